@@ -0,0 +1,82 @@
+use std::path::PathBuf;
+
+use crate::cli::parse_addr;
+
+// Commands typed at the game prompt after `admin`, which the VM runs itself instead of
+// handing the line to the program. Addresses and values are hex with 0x or decimal.
+
+pub const HELP: &str = "admin                    address and registers
+admin set $<r> <val>     set a register
+admin poke <addr> <val>  write a value to memory
+admin peek <addr> [len]  read values from memory
+admin stack              show the stack, top last
+admin dis <addr> [n]     disassemble instructions
+admin save <file>        write a snapshot
+admin load <file>        resume from a snapshot
+admin trace on|off       print every instruction run to stderr
+admin help               list these commands";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AdminCommand {
+    Status,
+    Set(usize, u16),
+    Poke(usize, u16),
+    Peek(usize, usize),
+    Stack,
+    Dis(usize, usize),
+    Save(PathBuf),
+    Load(PathBuf),
+    Trace(bool),
+    Help,
+}
+
+fn value(word: &str, below: usize) -> Result<usize, String> {
+    match parse_addr(word)? {
+        val if val < below => Ok(val),
+        val => Err(format!(
+            "{:#06x} is out of range, it must be below {:#06x}.",
+            val, below
+        )),
+    }
+}
+
+impl AdminCommand {
+    /// The command on an input line, if it is an admin line at all
+    pub fn parse(line: &str) -> Option<Result<Self, String>> {
+        let mut words = line.split_whitespace();
+        if words.next() != Some("admin") {
+            return None;
+        }
+        let words: Vec<&str> = words.collect();
+
+        let command = match words.as_slice() {
+            [] => Ok(Self::Status),
+            ["set", name, val] => match name.strip_prefix('$').and_then(|reg| reg.parse().ok()) {
+                Some(reg) if reg < 8 => value(val, 32768).map(|val| Self::Set(reg, val as u16)),
+                _ => Err(format!("{} is not a register from $0 to $7.", name)),
+            },
+            ["poke", addr, val] => {
+                value(addr, 32768).and_then(|addr| Ok(Self::Poke(addr, value(val, 32776)? as u16)))
+            }
+            ["peek", addr] => value(addr, 32768).map(|addr| Self::Peek(addr, 1)),
+            ["peek", addr, len] => {
+                value(addr, 32768).and_then(|addr| Ok(Self::Peek(addr, value(len, 32769)?)))
+            }
+            ["stack"] => Ok(Self::Stack),
+            ["dis", addr] => value(addr, 32768).map(|addr| Self::Dis(addr, 10)),
+            ["dis", addr, count] => {
+                value(addr, 32768).and_then(|addr| Ok(Self::Dis(addr, value(count, 32769)?)))
+            }
+            ["save", file] => Ok(Self::Save(PathBuf::from(file))),
+            ["load", file] => Ok(Self::Load(PathBuf::from(file))),
+            ["trace", "on"] => Ok(Self::Trace(true)),
+            ["trace", "off"] => Ok(Self::Trace(false)),
+            ["help"] => Ok(Self::Help),
+            _ => Err(format!(
+                "Unknown admin command `{}`, try `admin help`.",
+                words.join(" ")
+            )),
+        };
+        Some(command)
+    }
+}
@@ -0,0 +1,125 @@
+use std::path::{Path, PathBuf};
+
+use crate::cli::FileType;
+use crate::compile::{resolve, write_program, Arg, Asm};
+use crate::error::{ErrorKind, SynacorErr};
+use crate::fmt::render;
+use crate::opcodes::OpName;
+
+// Brainfuck on the Synacor VM. `$1` points into a tape of zeroed cells that starts after
+// the code and runs to the end of memory, and `$0` holds the current cell while it is
+// changed. Cells wrap at 256, runs of `+ - < >` become a single `add`, and every character
+// that is not a command is a comment. Reading past the end of input leaves the VM waiting
+// for more.
+
+use Arg::{Label as L, Lit, Reg};
+
+fn unmatched(code: &str, path: &Path, at: usize, expected: &'static str) -> SynacorErr {
+    SynacorErr::new_code(
+        at,
+        at + 1,
+        path.to_path_buf(),
+        code.to_string(),
+        ErrorKind::Parse {
+            lexeme: code[at..at + 1].to_string(),
+            expected,
+        },
+    )
+}
+
+/// Translate a Brainfuck program to canonical assembly
+pub fn bf2syn(code: &str, path: &Path) -> Result<String, SynacorErr> {
+    let commands: Vec<(usize, u8)> = code
+        .bytes()
+        .enumerate()
+        .filter(|(_, c)| b"+-<>.,[]".contains(c))
+        .collect();
+
+    let mut asm = vec![Asm::Ins(OpName::Set, vec![Reg(1), L("__tape".to_string())])];
+    // start of each open loop, by label number
+    let mut loops: Vec<(usize, usize)> = Vec::new();
+    let mut labels = 0;
+    let mut i = 0;
+
+    while i < commands.len() {
+        let (at, c) = commands[i];
+        i += 1;
+
+        match c {
+            b'+' | b'-' | b'<' | b'>' => {
+                let is_move = c == b'<' || c == b'>';
+                let step = |c: u8| match (c, is_move) {
+                    (b'+', false) | (b'>', true) => Some(1),
+                    (b'-', false) | (b'<', true) => Some(-1),
+                    _ => None,
+                };
+                let run: Vec<i32> = commands[i - 1..]
+                    .iter()
+                    .map_while(|(_, c)| step(*c))
+                    .collect();
+                i += run.len() - 1;
+                let delta: i32 = run.iter().sum();
+
+                if is_move {
+                    let delta = delta.rem_euclid(32768) as u16;
+                    if delta != 0 {
+                        asm.push(Asm::Ins(OpName::Add, vec![Reg(1), Reg(1), Lit(delta)]));
+                    }
+                } else {
+                    let delta = delta.rem_euclid(256) as u16;
+                    if delta != 0 {
+                        asm.extend([
+                            Asm::Ins(OpName::Rmem, vec![Reg(0), Reg(1)]),
+                            Asm::Ins(OpName::Add, vec![Reg(0), Reg(0), Lit(delta)]),
+                            Asm::Ins(OpName::And, vec![Reg(0), Reg(0), Lit(0xff)]),
+                            Asm::Ins(OpName::Wmem, vec![Reg(1), Reg(0)]),
+                        ]);
+                    }
+                }
+            }
+            b'.' => asm.extend([
+                Asm::Ins(OpName::Rmem, vec![Reg(0), Reg(1)]),
+                Asm::Ins(OpName::Out, vec![Reg(0)]),
+            ]),
+            b',' => asm.extend([
+                Asm::Ins(OpName::In, vec![Reg(0)]),
+                Asm::Ins(OpName::Wmem, vec![Reg(1), Reg(0)]),
+            ]),
+            b'[' => {
+                labels += 1;
+                loops.push((labels, at));
+                asm.extend([
+                    Asm::Label(format!(".L{}", labels)),
+                    Asm::Ins(OpName::Rmem, vec![Reg(0), Reg(1)]),
+                    Asm::Ins(OpName::Jf, vec![Reg(0), L(format!(".L{}_end", labels))]),
+                ]);
+            }
+            _ => {
+                let Some((label, _)) = loops.pop() else {
+                    return Err(unmatched(code, path, at, "the end of an open loop"));
+                };
+                asm.extend([
+                    Asm::Ins(OpName::Jmp, vec![L(format!(".L{}", label))]),
+                    Asm::Label(format!(".L{}_end", label)),
+                ]);
+            }
+        }
+    }
+
+    if let Some((_, at)) = loops.pop() {
+        return Err(unmatched(code, path, at, "a loop closed by `]`"));
+    }
+
+    asm.extend([
+        Asm::Ins(OpName::Halt, vec![]),
+        Asm::Label("__tape".to_string()),
+        Asm::Data(0),
+    ]);
+    Ok(render(&resolve(&asm)))
+}
+
+/// Translate a Brainfuck file to assembly or to a binary
+pub fn bf2syn_file(path: &PathBuf, out_path: &PathBuf, emit: &FileType) -> Result<(), SynacorErr> {
+    let code = std::fs::read_to_string(path)?;
+    write_program(&bf2syn(&code, path)?, out_path, emit)
+}
@@ -1,38 +1,26 @@
-use itertools::Itertools;
-use strum::IntoEnumIterator;
-use strum_macros::EnumIter;
+use std::path::Path;
 
-#[derive(EnumIter, Debug, PartialEq, Clone)]
-enum Coin {
-    Red,
-    Corroded,
-    Shiny,
-    Concave,
-    Blue,
-}
-
-impl Coin {
-    fn val(&self) -> u32 {
-        match self {
-            Self::Red => 2,
-            Self::Corroded => 3,
-            Self::Shiny => 5,
-            Self::Concave => 7,
-            Self::Blue => 9,
-        }
-    }
-}
+use synacor::slots::{Equation, Item};
 
 fn main() {
-    let res = Coin::iter()
-        .permutations(5)
-        .find(|vec| match vec.as_slice() {
-            [a, b, c, d, e] => {
-                399 == a.val() + b.val() * u32::pow(c.val(), 2) + u32::pow(d.val(), 3) - e.val()
-            }
-            _ => unreachable!(),
-        })
-        .unwrap();
+    let equation = Equation::parse("_ + _ * _^2 + _^3 - _ = 399", Path::new("<equation>"))
+        .expect("the monument's equation parses");
+    let items: Vec<Item> = [
+        ("red coin", 2),
+        ("corroded coin", 3),
+        ("shiny coin", 5),
+        ("concave coin", 7),
+        ("blue coin", 9),
+    ]
+    .into_iter()
+    .map(|(name, value)| Item {
+        name: name.to_string(),
+        value,
+    })
+    .collect();
 
-    println!("{:?}", res);
+    match equation.solve(&items) {
+        Some(order) => order.iter().for_each(|item| println!("use {}", item.name)),
+        None => println!("No order of the coins solves {}", equation.fill(&[])),
+    }
 }
@@ -1,34 +1,348 @@
 use clap::Parser;
-use main_error::MainError;
+use codespan_reporting::term::termcolor::ColorChoice;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::ExitCode;
 
-use synacor::cli::{Cli, Command, FileType};
-use synacor::convert::{asm_to_u16, bin_to_u16, u16_to_asm, u16_to_bin};
-use synacor::vm::VM;
+use synacor::bf::bf2syn_file;
+use synacor::cfg::u16_to_dot;
+use synacor::cli::{exit_code, Cli, Command, FileType, MessageFormat, OnEof};
+use synacor::codes::{harvest, report};
+use synacor::compile::compile_file;
+use synacor::convert::{asm_to_u16_with_map, bin_to_u16, disassembly_map, u16_to_asm, u16_to_bin};
+use synacor::decompile::decompile;
+use synacor::diff::{diff, Image};
+use synacor::disasm::Instruction;
+use synacor::error::{ErrorKind, SynacorErr};
+use synacor::fmt;
+use synacor::lint::{emit_warnings, lint, Level, LintConfig};
+use synacor::lsp::serve;
+use synacor::optimize::optimize;
+use synacor::patch::{apply, parse_patch_file, Hunk};
+use synacor::prompt::Prompt;
+use synacor::script::Script;
+use synacor::session::{Recorder, Replay};
+use synacor::slots::{scrape, Equation};
+use synacor::snapshot::Snapshot;
+use synacor::solve::write_solution;
+use synacor::strings::{changed_ranges, decrypted_image, find_strings};
+use synacor::vault::{scrape as scrape_vault, Vault};
+use synacor::vm::{EofPolicy, VM};
+use synacor::world::write_world;
+use synacor::xref::Xrefs;
 
-fn main() -> Result<(), MainError> {
+fn main() -> ExitCode {
     let args = Cli::parse();
+    let (format, color) = (args.message_format, args.color.choice());
 
-    // can't use ? here because of codespan-reporting
-    let read_memory = match args.ftype {
-        FileType::Binary => bin_to_u16(&args.path),
-        FileType::Assembly => asm_to_u16(&args.path),
+    let result = match args.command {
+        Command::Lsp => serve(),
+        Command::Compile {
+            ref source,
+            ref out_path,
+            ref emit,
+        } => compile_file(source, out_path, emit),
+        Command::Bf2syn {
+            ref source,
+            ref out_path,
+            ref emit,
+        } => bf2syn_file(source, out_path, emit),
+        _ => {
+            let (ftype, path) = args.input().unwrap_or_else(|e| e.exit());
+            run(args.command, ftype, path, format, color)
+        }
     };
 
-    match read_memory {
-        Err(e) => e.emit()?,
-        Ok(memory) => match (args.command, args.ftype) {
-            (Command::Run { auto }, _) => {
-                let mut vm = VM::new(memory, auto);
-                vm.run()?;
-            }
-            (Command::Convert { out_path }, FileType::Binary) => u16_to_asm(memory, &out_path)?,
-            (Command::Convert { out_path }, FileType::Assembly) => {
-                match u16_to_bin(memory, &out_path) {
-                    Err(e) => e.emit()?,
-                    _ => (),
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            // anything the program printed belongs before the report
+            let _ = std::io::stdout().flush();
+            if let Err(emit_err) = e.emit(format, color) {
+                eprintln!("{}", emit_err);
+            }
+            ExitCode::from(exit_code(&e.kind))
+        }
+    }
+}
+
+fn run(
+    command: Command,
+    ftype: FileType,
+    path: PathBuf,
+    format: MessageFormat,
+    color: ColorChoice,
+) -> Result<(), SynacorErr> {
+    // assembly keeps its source map so runtime errors can point at the faulting line
+    let (memory, source_map) = match ftype {
+        FileType::Binary => (bin_to_u16(&path)?, None),
+        FileType::Assembly => {
+            let (memory, map) = asm_to_u16_with_map(&path)?;
+            (memory, Some(map))
+        }
+    };
+
+    match (command, ftype) {
+        (
+            Command::Run {
+                auto,
+                script,
+                record,
+                replay,
+                on_eof,
+                eof_line,
+                readline,
+                history,
+            },
+            _,
+        ) => {
+            let mut vm = VM::new(memory, auto);
+            if readline {
+                let history = history.unwrap_or_else(Prompt::default_history);
+                vm.set_prompt(Prompt::new(&history)?);
+            }
+            vm.set_eof_policy(match on_eof {
+                OnEof::Halt => EofPolicy::Halt,
+                OnEof::Error => EofPolicy::Error,
+                OnEof::Feed => EofPolicy::Feed(eof_line),
+            });
+            if let Some(script) = script {
+                vm.set_script(Script::load(&script)?);
+            }
+            if let Some(record) = record {
+                vm.record(Recorder::create(&record)?);
+            }
+            if let Some(replay) = replay {
+                vm.replay(Replay::load(&replay)?);
+            }
+            if let Err(e) = vm.run() {
+                return Err(match &source_map {
+                    Some(map) => e.with_source(map),
+                    None => e,
+                });
+            }
+        }
+        (
+            Command::Map {
+                json,
+                dot,
+                room_addr,
+                max_rooms,
+            },
+            _,
+        ) => write_world(
+            memory,
+            room_addr,
+            max_rooms,
+            json.as_deref(),
+            dot.as_deref(),
+        )?,
+        (
+            Command::Solve {
+                goal,
+                out_path,
+                room_addr,
+                max_states,
+            },
+            _,
+        ) => write_solution(memory, &goal, room_addr, max_states, &out_path)?,
+        (Command::Slots { equation, kind }, _) => {
+            let (scraped, items) = scrape(memory, &kind)?;
+            let equation = match (equation, scraped) {
+                (Some(text), _) => Equation::parse(&text, &PathBuf::from("<equation>"))?,
+                (None, Some(scraped)) => scraped,
+                (None, None) => {
+                    return Err(SynacorErr::new_plain(ErrorKind::Solve(
+                        "No equation among the program's strings, pass one with --equation."
+                            .to_string(),
+                    )))
+                }
+            };
+
+            let Some(order) = equation.solve(&items) else {
+                return Err(SynacorErr::new_plain(ErrorKind::Solve(format!(
+                    "No order of the {} items named `{}` solves {}.",
+                    items.len(),
+                    kind,
+                    equation.fill(&[])
+                ))));
+            };
+            let values: Vec<i64> = order.iter().map(|item| item.value).collect();
+            println!("{}", equation.fill(&values));
+            for item in order {
+                println!("use {}", item.name);
+            }
+        }
+        (Command::Vault { grid, snapshot }, _) => {
+            let vault = match (grid, snapshot) {
+                (Some(grid), _) => Vault::parse(&std::fs::read_to_string(&grid)?, &grid)?,
+                (_, Some(snapshot)) => scrape_vault(&Snapshot::load(&snapshot)?)?,
+                _ => unreachable!(),
+            };
+
+            let Some(moves) = vault.solve() else {
+                return Err(SynacorErr::new_plain(ErrorKind::Solve(format!(
+                    "No walk through the vault lock reaches the door weighing {}.",
+                    vault.target
+                ))));
+            };
+            println!("{}", vault.equation(&moves));
+            println!("take orb");
+            for name in moves {
+                println!("{}", name);
+            }
+        }
+        (
+            Command::Codes {
+                script,
+                json,
+                expect,
+            },
+            _,
+        ) => {
+            let script = match script {
+                Some(script) => Script::load(&script)?,
+                None => Script::solution(),
+            };
+            report(&harvest(memory, script)?, json, expect.as_deref())?;
+        }
+        (Command::Convert { out_path }, FileType::Binary) => u16_to_asm(memory, &out_path)?,
+        (Command::Convert { out_path }, FileType::Assembly) => u16_to_bin(memory, &out_path)?,
+        (Command::Cfg { out_path, split }, _) => u16_to_dot(memory, &out_path, split)?,
+        (Command::Decompile { out_path, function }, _) => {
+            let code = decompile(&memory, function);
+            match out_path {
+                Some(out_path) => {
+                    std::fs::write(&out_path, code)?;
+                    println!("Created pseudo-code file {}", out_path.display());
+                }
+                None => print!("{}", code),
+            }
+        }
+        (Command::Diff { other }, ftype) => {
+            let a = Image::read(&path, &ftype)?;
+            let b = Image::read(&other, &ftype)?;
+            print!("{}", diff(&a, &b));
+        }
+        (Command::Optimize { out_path }, _) => {
+            let optimized = optimize(&memory)?;
+            println!(
+                "Optimized {} values down to {}",
+                memory.len(),
+                optimized.len()
+            );
+            u16_to_bin(optimized, &out_path)?;
+        }
+        (
+            Command::Patch {
+                at,
+                asm,
+                file,
+                force,
+                out_path,
+            },
+            _,
+        ) => {
+            let hunks = match (at, asm, file) {
+                (Some(at), Some(asm), _) => vec![Hunk::from_asm(at, &asm)?],
+                (_, _, Some(file)) => parse_patch_file(&file)?,
+                _ => unreachable!(),
+            };
+
+            let mut patched = memory.clone();
+            for hunk in hunks.iter() {
+                apply(&mut patched, hunk, force)?;
+                println!("Patched {} values at {:#06x}", hunk.words.len(), hunk.addr);
+            }
+            u16_to_bin(patched, &out_path)?;
+        }
+        (Command::Strings { min_len, out_path }, _) => {
+            let decrypted = decrypted_image(memory.clone())?;
+
+            println!("{:<8} {:<5} String", "Address", "Len");
+            for range in changed_ranges(&memory, &decrypted) {
+                for string in find_strings(&decrypted, range, min_len) {
+                    println!(
+                        "{:#06x}   {:<5} \"{}\"",
+                        string.addr,
+                        string.text.len(),
+                        string.escaped()
+                    );
+                }
+            }
+
+            if let Some(out_path) = out_path {
+                u16_to_bin(decrypted, &out_path)?;
+            }
+        }
+        (Command::Fmt { check }, FileType::Assembly) => {
+            let asm = std::fs::read_to_string(&path)?;
+            let formatted = fmt::format(&asm, &path)?;
+
+            if check {
+                if formatted != asm {
+                    for (line, removed, added) in fmt::changes(&asm, &formatted) {
+                        println!("{}:{}", path.display(), line);
+                        for old in removed {
+                            println!("- {}", old);
+                        }
+                        for new in added {
+                            println!("+ {}", new);
+                        }
+                    }
+                    return Err(SynacorErr::new_plain(ErrorKind::Unformatted(
+                        path.display().to_string(),
+                    )));
+                }
+            } else if formatted != asm {
+                std::fs::write(&path, formatted)?;
+                println!("Formatted {}", path.display());
+            }
+        }
+        (Command::Fmt { .. }, FileType::Binary) => {
+            unreachable!("binaries are refused when parsing the arguments")
+        }
+        (Command::Lint { allow, deny }, _) => {
+            let mut config = LintConfig::default();
+            for lint in allow {
+                config.set(lint, Level::Allow);
+            }
+            for lint in deny {
+                config.set(lint, Level::Deny);
+            }
+
+            // binaries are reported against their disassembly
+            let map = match source_map {
+                Some(map) => map,
+                None => {
+                    let listing = PathBuf::from(format!("{} (disassembly)", path.display()));
+                    disassembly_map(&memory, &listing)?
+                }
+            };
+
+            let warnings = lint(&memory, &config);
+            let denied = emit_warnings(&warnings, &map, &config, format, color)
+                .map_err(|e| SynacorErr::new_io(ErrorKind::Io(e.to_string())))?;
+            if format == MessageFormat::Human {
+                eprintln!("{} lint warnings", warnings.len());
+            }
+            if denied > 0 {
+                return Err(SynacorErr::new_plain(ErrorKind::LintDenied(denied)));
+            }
+        }
+        (Command::Lsp, _) | (Command::Compile { .. }, _) | (Command::Bf2syn { .. }, _) => {
+            unreachable!("the language server and compilers run without an input file")
+        }
+        (Command::Xref { addr }, _) => {
+            let xrefs = Xrefs::new(&memory);
+            println!("References to {:#06x}:", addr);
+            for r in xrefs.to(addr) {
+                match Instruction::decode(&memory, r.from) {
+                    Some(ins) => println!("  {:<5} {:#06x}: {}", r.kind, r.from, ins),
+                    None => println!("  {:<5} {:#06x}", r.kind, r.from),
                 }
             }
-        },
+        }
     }
 
     Ok(())
@@ -0,0 +1,319 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as FmtWrite;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+use crate::disasm::{Instruction, Operand};
+use crate::error::SynacorErr;
+use crate::opcodes::OpName;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeKind {
+    Jump,
+    Taken,
+    NotTaken,
+    Fallthrough,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edge {
+    pub target: usize,
+    pub kind: EdgeKind,
+}
+
+#[derive(Debug, Clone)]
+pub struct BasicBlock {
+    pub start: usize,
+    pub instructions: Vec<Instruction>,
+    pub successors: Vec<Edge>,
+    /// Resolved targets of every `call` in the block
+    pub calls: Vec<usize>,
+}
+
+impl BasicBlock {
+    pub fn last(&self) -> &Instruction {
+        // blocks are never empty
+        self.instructions.last().unwrap()
+    }
+}
+
+// registers holding a known literal along the current path, enough to follow
+// the `set $0 <addr>; call $0` indirection used by the challenge's self-test
+type Known = [Option<u16>; 8];
+
+fn resolve(ins: &Instruction, known: &Known) -> Option<usize> {
+    match ins.target_operand()? {
+        Operand::Literal(val) => Some(val as usize),
+        Operand::Register(reg) => known[reg as usize].map(|val| val as usize),
+        Operand::Invalid(_) => None,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Function {
+    pub entry: usize,
+    pub blocks: BTreeMap<usize, BasicBlock>,
+    /// Resolved destination of every jump and call, by instruction address
+    pub targets: BTreeMap<usize, usize>,
+}
+
+impl Function {
+    fn discover(memory: &[u16], entry: usize) -> Self {
+        let mut instructions: BTreeMap<usize, Instruction> = BTreeMap::new();
+        let mut targets: BTreeMap<usize, usize> = BTreeMap::new();
+        let mut leaders = BTreeSet::from([entry]);
+        let mut todo: Vec<(usize, Known)> = vec![(entry, [None; 8])];
+
+        while let Some((addr, mut known)) = todo.pop() {
+            if instructions.contains_key(&addr) {
+                continue;
+            }
+
+            // invalid opcodes end the path, most likely we walked into data
+            let Some(ins) = Instruction::decode(memory, addr) else {
+                continue;
+            };
+
+            let target = resolve(&ins, &known);
+            targets.extend(target.map(|target| (addr, target)));
+
+            if let Some(reg) = ins.dest() {
+                known[reg as usize] = match (ins.opname, ins.operands.get(1)) {
+                    (OpName::Set, Some(Operand::Literal(val))) => Some(*val),
+                    _ => None,
+                };
+            }
+
+            match ins.opname {
+                OpName::Jmp => {
+                    leaders.extend(target);
+                    todo.extend(target.map(|target| (target, [None; 8])));
+                }
+                OpName::Jt | OpName::Jf => {
+                    leaders.extend(target);
+                    leaders.insert(ins.next_addr());
+                    todo.extend(target.map(|target| (target, [None; 8])));
+                    todo.push((ins.next_addr(), [None; 8]));
+                }
+                // the callee may clobber any register
+                OpName::Call => todo.push((ins.next_addr(), [None; 8])),
+                OpName::Halt | OpName::Ret => (),
+                _ => todo.push((ins.next_addr(), known)),
+            }
+
+            instructions.insert(addr, ins);
+        }
+
+        let mut blocks = BTreeMap::new();
+
+        for &start in leaders.iter() {
+            let mut block = BasicBlock {
+                start,
+                instructions: Vec::new(),
+                successors: Vec::new(),
+                calls: Vec::new(),
+            };
+            let mut addr = start;
+
+            while let Some(ins) = instructions.get(&addr) {
+                block.instructions.push(ins.clone());
+                addr = ins.next_addr();
+
+                let target = targets.get(&ins.addr).copied();
+                match ins.opname {
+                    OpName::Jmp => {
+                        block.successors.extend(target.map(|target| Edge {
+                            target,
+                            kind: EdgeKind::Jump,
+                        }));
+                        break;
+                    }
+                    OpName::Jt | OpName::Jf => {
+                        block.successors.extend(target.map(|target| Edge {
+                            target,
+                            kind: EdgeKind::Taken,
+                        }));
+                        block.successors.push(Edge {
+                            target: addr,
+                            kind: EdgeKind::NotTaken,
+                        });
+                        break;
+                    }
+                    OpName::Halt | OpName::Ret => break,
+                    _ => {
+                        block.calls.extend(target);
+                        if leaders.contains(&addr) && instructions.contains_key(&addr) {
+                            block.successors.push(Edge {
+                                target: addr,
+                                kind: EdgeKind::Fallthrough,
+                            });
+                            break;
+                        }
+                    }
+                }
+            }
+
+            if !block.instructions.is_empty() {
+                blocks.insert(start, block);
+            }
+        }
+
+        Self {
+            entry,
+            blocks,
+            targets,
+        }
+    }
+
+    /// Resolved targets of every `call` in the function
+    pub fn calls(&self) -> BTreeSet<usize> {
+        self.blocks
+            .values()
+            .flat_map(|block| block.calls.iter().copied())
+            .collect()
+    }
+
+    pub fn name(&self) -> String {
+        format!("fn_{:#06x}", self.entry)
+    }
+
+    fn write_nodes(&self, dot: &mut String, indent: &str) {
+        for block in self.blocks.values() {
+            let label: String = block
+                .instructions
+                .iter()
+                .map(|ins| format!("{:#06x}: {}\\l", ins.addr, ins))
+                .collect();
+            let _ = writeln!(
+                dot,
+                "{}\"{}\" [label=\"{}\"];",
+                indent,
+                node_id(self.entry, block.start),
+                label
+            );
+        }
+    }
+
+    fn write_edges(&self, dot: &mut String, indent: &str) {
+        for block in self.blocks.values() {
+            for edge in block.successors.iter() {
+                let attrs = match (edge.kind, block.last().opname) {
+                    (EdgeKind::Taken, OpName::Jt) | (EdgeKind::NotTaken, OpName::Jf) => {
+                        " [label=\"true\", color=darkgreen]"
+                    }
+                    (EdgeKind::Taken, _) | (EdgeKind::NotTaken, _) => {
+                        " [label=\"false\", color=red]"
+                    }
+                    _ => "",
+                };
+                let _ = writeln!(
+                    dot,
+                    "{}\"{}\" -> \"{}\"{};",
+                    indent,
+                    node_id(self.entry, block.start),
+                    node_id(self.entry, edge.target),
+                    attrs
+                );
+            }
+        }
+    }
+
+    pub fn to_dot(&self) -> String {
+        let mut dot = format!("digraph \"{}\" {{\n", self.name());
+        dot.push_str("  node [shape=box, fontname=\"monospace\"];\n");
+        self.write_nodes(&mut dot, "  ");
+        self.write_edges(&mut dot, "  ");
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+// blocks may be shared between functions, so node names include the function
+fn node_id(function: usize, block: usize) -> String {
+    format!("{:#06x}_{:#06x}", function, block)
+}
+
+/// Basic blocks of every function reachable from the given entry points
+#[derive(Debug, Clone)]
+pub struct Cfg {
+    pub functions: BTreeMap<usize, Function>,
+}
+
+impl Cfg {
+    /// Program starting at address 0, as loaded by the VM
+    pub fn new(memory: &[u16]) -> Self {
+        Self::with_entries(memory, &[0])
+    }
+
+    pub fn with_entries(memory: &[u16], entries: &[usize]) -> Self {
+        let mut functions = BTreeMap::new();
+        let mut todo = entries.to_vec();
+
+        while let Some(entry) = todo.pop() {
+            if functions.contains_key(&entry) || entry >= memory.len() {
+                continue;
+            }
+            let function = Function::discover(memory, entry);
+            todo.extend(function.calls());
+            functions.insert(entry, function);
+        }
+
+        Self { functions }
+    }
+
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph program {\n");
+        dot.push_str("  node [shape=box, fontname=\"monospace\"];\n");
+
+        for function in self.functions.values() {
+            let _ = writeln!(dot, "  subgraph \"cluster_{}\" {{", function.name());
+            let _ = writeln!(dot, "    label=\"{}\";", function.name());
+            function.write_nodes(&mut dot, "    ");
+            function.write_edges(&mut dot, "    ");
+            dot.push_str("  }\n");
+        }
+
+        for function in self.functions.values() {
+            for block in function.blocks.values() {
+                for &callee in block.calls.iter().collect::<BTreeSet<&usize>>() {
+                    if self.functions.contains_key(&callee) {
+                        let _ = writeln!(
+                            dot,
+                            "  \"{}\" -> \"{}\" [style=dashed];",
+                            node_id(function.entry, block.start),
+                            node_id(callee, callee)
+                        );
+                    }
+                }
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Write the control-flow graph either as a single file, or one file per function into a directory
+pub fn u16_to_dot(memory: Vec<u16>, out_path: &Path, split: bool) -> Result<(), SynacorErr> {
+    let cfg = Cfg::new(&memory);
+
+    if split {
+        std::fs::create_dir_all(out_path)?;
+        for function in cfg.functions.values() {
+            let mut file = File::create(out_path.join(format!("{}.dot", function.name())))?;
+            write!(file, "{}", function.to_dot())?;
+        }
+        println!(
+            "Created {} DOT files in {}",
+            cfg.functions.len(),
+            out_path.display()
+        );
+    } else {
+        let mut file = File::create(out_path)?;
+        write!(file, "{}", cfg.to_dot())?;
+        println!("Created DOT file {}", out_path.display());
+    }
+
+    Ok(())
+}
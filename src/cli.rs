@@ -1,13 +1,44 @@
-use clap::{Parser, Subcommand, ValueEnum};
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
 use std::path::PathBuf;
 use strum_macros::Display;
 
+use crate::error::ErrorKind;
+pub use crate::error::{ColorWhen, MessageFormat};
+use crate::lint::Lint;
+
 #[derive(Subcommand, Clone, Debug)]
 pub enum Command {
     /// Run a given binary or assembly file
     Run {
-        #[arg(long)]
+        /// Play the built-in walkthrough up to the teleporter
+        #[arg(long, conflicts_with = "script")]
         auto: bool,
+
+        /// Play the lines and directives of a script file before handing input to the player
+        #[arg(long)]
+        script: Option<PathBuf>,
+
+        /// Log every input line with the instruction count and output hash it was read at
+        #[arg(long)]
+        record: Option<PathBuf>,
+
+        /// Play back a recorded session, failing when the run stops matching it
+        #[arg(long, conflicts_with_all = ["auto", "script", "record"])]
+        replay: Option<PathBuf>,
+        /// What to do when stdin ends while the program waits for input
+        #[arg(long, value_enum, default_value_t = OnEof::Halt)]
+        on_eof: OnEof,
+
+        /// Line typed for each read after stdin ends, with `--on-eof feed`
+        #[arg(long, default_value = "")]
+        eof_line: String,
+        /// Read lines with editing, history and tab completion when stdin is a terminal
+        #[arg(long)]
+        readline: bool,
+
+        /// History file for `--readline`, `~/.synacor_history` if not given
+        #[arg(long, requires = "readline")]
+        history: Option<PathBuf>,
     },
 
     /// Convert a file from binary to assembly or vice versa
@@ -16,6 +47,220 @@ pub enum Command {
         #[arg(short, long)]
         out_path: PathBuf,
     },
+
+    /// Export the control-flow graph as Graphviz DOT
+    Cfg {
+        /// Output path, a directory when splitting by function
+        #[arg(short, long)]
+        out_path: PathBuf,
+
+        /// Write one DOT file per function instead of a single graph
+        #[arg(long)]
+        split: bool,
+    },
+
+    /// Lift functions into C-like pseudo-code
+    Decompile {
+        /// Output path, printed to stdout if not given
+        #[arg(short, long)]
+        out_path: Option<PathBuf>,
+
+        /// Only decompile the function starting at this address
+        #[arg(long, value_parser = parse_addr)]
+        function: Option<usize>,
+    },
+
+    /// Compare the program or snapshot against another one
+    Diff {
+        /// The program or snapshot to compare against, of the same file type
+        other: PathBuf,
+    },
+
+    /// Explore every exit of the text adventure and write a map of its rooms
+    Map {
+        /// Output path for the map as JSON
+        #[arg(long, required_unless_present = "dot")]
+        json: Option<PathBuf>,
+
+        /// Output path for the map as Graphviz DOT
+        #[arg(long)]
+        dot: Option<PathBuf>,
+
+        /// Address holding the current room, 0x0ac2 in challenge.bin, to tell apart rooms
+        /// that read the same
+        #[arg(long, value_parser = parse_addr)]
+        room_addr: Option<usize>,
+
+        /// Stop adding rooms after this many, leaving the exits to new ones unexplored
+        #[arg(long, default_value_t = 1000)]
+        max_rooms: usize,
+    },
+
+    /// Search for the commands that make the text adventure print some text, as a script
+    Solve {
+        /// Text the game should print, such as `teleporter` or a code
+        #[arg(long)]
+        goal: String,
+
+        /// Output path for the script of commands
+        #[arg(short, long)]
+        out_path: PathBuf,
+
+        /// Address holding the current room, 0x0ac2 in challenge.bin, to tell apart rooms
+        /// that read the same, instead of looking for it in memory
+        #[arg(long, value_parser = parse_addr)]
+        room_addr: Option<usize>,
+
+        /// Give up after this many game states
+        #[arg(long, default_value_t = 100_000)]
+        max_states: usize,
+    },
+
+    /// Find the order to place items in the blanks of an equation, such as the coins
+    Slots {
+        /// Equation with `_` for blanks, instead of the first one among the program's strings
+        #[arg(long)]
+        equation: Option<String>,
+
+        /// End of the names of the items to place
+        #[arg(long, default_value = "coin")]
+        kind: String,
+    },
+
+    /// Find the fewest moves through the vault lock that carry the orb to the door
+    Vault {
+        /// Grid file with the rows of rooms from north to south and a `target` line
+        #[arg(
+            long,
+            required_unless_present = "snapshot",
+            conflicts_with = "snapshot"
+        )]
+        grid: Option<PathBuf>,
+
+        /// Snapshot taken in the vault antechamber, to read the grid by walking the rooms
+        #[arg(long)]
+        snapshot: Option<PathBuf>,
+    },
+
+    /// Play through the game with a script and list the codes it prints at each stage
+    Codes {
+        /// Script to play instead of the built-in walkthrough
+        #[arg(long)]
+        script: Option<PathBuf>,
+
+        /// Print the codes as JSON
+        #[arg(long)]
+        json: bool,
+
+        /// Fail unless the codes match those saved from `codes --json`
+        #[arg(long)]
+        expect: Option<PathBuf>,
+    },
+
+    /// Shrink the program with peephole optimizations and write it as a binary
+    Optimize {
+        /// Output path for the optimized binary
+        #[arg(short, long)]
+        out_path: PathBuf,
+    },
+
+    /// Assemble replacement instructions over part of the program
+    Patch {
+        /// Address of the first replaced instruction, in hex with a 0x prefix or decimal
+        #[arg(long, value_parser = parse_addr, requires = "asm", conflicts_with = "file")]
+        at: Option<usize>,
+
+        /// Replacement instructions, as assembly
+        #[arg(long, requires = "at")]
+        asm: Option<String>,
+
+        /// Patch file made of `@<addr>` hunks of assembly, with addresses read like `--at`
+        #[arg(long, required_unless_present = "at")]
+        file: Option<PathBuf>,
+
+        /// Skip the instruction boundary checks
+        #[arg(long)]
+        force: bool,
+
+        /// Output path for the patched binary
+        #[arg(short, long)]
+        out_path: PathBuf,
+    },
+
+    /// Dump the strings the program decrypts at startup
+    Strings {
+        /// Shortest string to report
+        #[arg(long, default_value_t = 4)]
+        min_len: usize,
+
+        /// Also write the decrypted memory image as a binary
+        #[arg(short, long)]
+        out_path: Option<PathBuf>,
+    },
+
+    /// Rewrite an assembly file in the canonical format
+    Fmt {
+        /// Report unformatted lines and fail instead of rewriting the file
+        #[arg(long)]
+        check: bool,
+    },
+
+    /// Warn about suspicious code, such as unreachable instructions or stack underflows
+    Lint {
+        /// Lints to turn off
+        #[arg(long, value_enum)]
+        allow: Vec<Lint>,
+
+        /// Lints to report as errors, failing the command
+        #[arg(long, value_enum)]
+        deny: Vec<Lint>,
+    },
+
+    /// Compile a program in the structured language to assembly or a binary
+    Compile {
+        /// Source file to compile
+        source: PathBuf,
+
+        /// Output path
+        #[arg(short, long)]
+        out_path: PathBuf,
+
+        /// Kind of file to write
+        #[arg(long, value_enum, default_value_t = FileType::Binary)]
+        emit: FileType,
+    },
+
+    /// Translate a Brainfuck program to assembly or a binary
+    Bf2syn {
+        /// Brainfuck source file
+        source: PathBuf,
+
+        /// Output path
+        #[arg(short, long)]
+        out_path: PathBuf,
+
+        /// Kind of file to write
+        #[arg(long, value_enum, default_value_t = FileType::Binary)]
+        emit: FileType,
+    },
+
+    /// Serve the Language Server Protocol on stdio for assembly files
+    Lsp,
+
+    /// List the instructions that jump to, call, read or write an address
+    Xref {
+        /// Address in hex (0x prefix) or decimal
+        #[arg(value_parser = parse_addr)]
+        addr: usize,
+    },
+}
+
+pub fn parse_addr(s: &str) -> Result<usize, String> {
+    let parsed = match s.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    parsed.map_err(|e| format!("\"{}\" is not a valid address: {}", s, e))
 }
 
 #[derive(ValueEnum, Display, Clone, Debug)]
@@ -34,16 +279,82 @@ impl FileType {
     }
 }
 
+#[derive(ValueEnum, Display, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[strum(serialize_all = "lowercase")]
+pub enum OnEof {
+    /// Stop cleanly, as if the program halted
+    #[default]
+    Halt,
+    /// Fail with an end of input error
+    Error,
+    /// Keep typing `--eof-line`
+    Feed,
+}
+
+/// Process exit code for each kind of error, so scripts can tell failures apart
+pub fn exit_code(kind: &ErrorKind) -> u8 {
+    match kind {
+        ErrorKind::Io(_) => 10,
+        ErrorKind::Parse { .. } => 11,
+        ErrorKind::InvalidValue(..) => 12,
+        ErrorKind::InvalidOpcode(_) => 13,
+        ErrorKind::InvalidRegister(_) => 14,
+        ErrorKind::StackUnderflow(_) => 15,
+        ErrorKind::MemoryOutOfRange(_) => 16,
+        ErrorKind::InvalidAscii(_) => 17,
+        ErrorKind::DivisionByZero(_) => 18,
+        ErrorKind::Patch(_) => 19,
+        ErrorKind::Snapshot(_) => 20,
+        ErrorKind::Unformatted(_) => 21,
+        ErrorKind::LintDenied(_) => 22,
+        ErrorKind::Optimize(_) => 23,
+        ErrorKind::Compile(_) => 24,
+        ErrorKind::Replay(_) => 25,
+        ErrorKind::EndOfInput => 26,
+        ErrorKind::Solve(_) => 27,
+        ErrorKind::Codes(_) => 28,
+        ErrorKind::StepLimit(_) => 29,
+    }
+}
+
 /// A Rust Implementation of the Synacor VM
 #[derive(Parser)]
 pub struct Cli {
+    /// Required by every command except `compile`, `bf2syn` and `lsp`
     #[arg(short, long)]
-    pub ftype: FileType,
+    pub ftype: Option<FileType>,
 
-    /// Input file path
+    /// Input file path, required by every command except `compile`, `bf2syn` and `lsp`
     #[arg(short, long)]
-    pub path: PathBuf,
+    pub path: Option<PathBuf>,
+
+    /// Format of error reports
+    #[arg(long, value_enum, default_value_t = MessageFormat::Human, global = true)]
+    pub message_format: MessageFormat,
+
+    /// When to color error reports
+    #[arg(long, value_enum, default_value_t = ColorWhen::Auto, global = true)]
+    pub color: ColorWhen,
 
     #[command(subcommand)]
     pub command: Command,
 }
+
+impl Cli {
+    /// File type and path of the input, which only the compilers and language server do without
+    pub fn input(&self) -> Result<(FileType, PathBuf), clap::Error> {
+        match (&self.ftype, &self.path) {
+            (Some(FileType::Binary), Some(_)) if matches!(self.command, Command::Fmt { .. }) => {
+                Err(Self::command().error(
+                    clap::error::ErrorKind::InvalidValue,
+                    "only assembly files can be formatted, pass --ftype assembly",
+                ))
+            }
+            (Some(ftype), Some(path)) => Ok((ftype.clone(), path.clone())),
+            _ => Err(Self::command().error(
+                clap::error::ErrorKind::MissingRequiredArgument,
+                "--ftype and --path are required for this command",
+            )),
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use std::path::Path;
+
+use serde_json::{json, Value};
+
+use crate::error::{ErrorKind, SynacorErr};
+use crate::script::Script;
+use crate::vm::VM;
+
+// The challenge prints a code at each stage, always after the same phrase:
+//
+// website     this one into the challenge website: ImoFztWQCvxj
+// self-test   The self-test completion code is: BNCyODLfQkIl
+// tablet      You find yourself writing "pWDWTEfURAdS" on the tablet.
+// maze        Chiseled on the wall of one of the passageways, you see:
+// teleporter  you think you see a pattern in the stars...
+// beach       Someone seems to have drawn a message in the sand here:
+// mirror      Through the mirror, you see "..." scrawled in charcoal on your forehead.
+//
+// The first of the eight codes is in the architecture spec rather than the program. The
+// mirror code is printed as seen in the mirror, so it is given reversed with b and d, and p
+// and q, swapped back.
+
+const STAGES: [(&str, &str); 7] = [
+    ("website", "this one into the challenge website:"),
+    ("self-test", "The self-test completion code is:"),
+    ("tablet", "You find yourself writing \""),
+    (
+        "maze",
+        "Chiseled on the wall of one of the passageways, you see:",
+    ),
+    ("teleporter", "you think you see a pattern in the stars..."),
+    (
+        "beach",
+        "Someone seems to have drawn a message in the sand here:",
+    ),
+    ("mirror", "Through the mirror, you see \""),
+];
+const CODE_LEN: usize = 12;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Code {
+    pub stage: String,
+    pub code: String,
+}
+
+impl Code {
+    pub fn to_json(&self) -> Value {
+        json!({ "stage": self.stage, "code": self.code })
+    }
+}
+
+fn unmirror(code: &str) -> String {
+    code.chars()
+        .rev()
+        .map(|c| match c {
+            'b' => 'd',
+            'd' => 'b',
+            'p' => 'q',
+            'q' => 'p',
+            c => c,
+        })
+        .collect()
+}
+
+/// Every code in the output, in the order printed
+pub fn find_codes(output: &str) -> Vec<Code> {
+    let mut codes: Vec<(usize, Code)> = Vec::new();
+
+    for (stage, phrase) in STAGES {
+        for (at, _) in output.match_indices(phrase) {
+            let rest = output[at + phrase.len()..].trim_start();
+            let code: String = rest
+                .chars()
+                .take_while(|c| c.is_ascii_alphanumeric())
+                .collect();
+            if code.len() != CODE_LEN {
+                continue;
+            }
+
+            let code = match stage {
+                "mirror" => unmirror(&code),
+                _ => code,
+            };
+            let stage = stage.to_string();
+            codes.push((at, Code { stage, code }));
+        }
+    }
+
+    codes.sort_by_key(|(at, _)| *at);
+    codes.into_iter().map(|(_, code)| code).collect()
+}
+
+/// Play the script until it runs out or the program halts, and collect the codes printed
+pub fn harvest(memory: Vec<u16>, script: Script) -> Result<Vec<Code>, SynacorErr> {
+    let mut vm = VM::headless(memory);
+    vm.set_script(script);
+    vm.run()?;
+    Ok(find_codes(&vm.take_output()))
+}
+
+/// Codes saved from `codes --json`
+pub fn load_expected(path: &Path) -> Result<Vec<Code>, SynacorErr> {
+    let text = std::fs::read_to_string(path)?;
+    let invalid = || {
+        SynacorErr::new_plain(ErrorKind::Codes(format!(
+            "{} is not a list of codes from `codes --json`.",
+            path.display()
+        )))
+    };
+
+    let value: Value = serde_json::from_str(&text).map_err(|_| invalid())?;
+    let entries = value.as_array().ok_or_else(invalid)?;
+    entries
+        .iter()
+        .map(
+            |entry| match (entry["stage"].as_str(), entry["code"].as_str()) {
+                (Some(stage), Some(code)) => Ok(Code {
+                    stage: stage.to_string(),
+                    code: code.to_string(),
+                }),
+                _ => Err(invalid()),
+            },
+        )
+        .collect()
+}
+
+/// Print the codes as a table or JSON, failing if they differ from the expected ones
+pub fn report(codes: &[Code], json: bool, expected: Option<&Path>) -> Result<(), SynacorErr> {
+    if json {
+        let codes: Vec<Value> = codes.iter().map(Code::to_json).collect();
+        println!("{:#}", Value::Array(codes));
+    } else {
+        println!("{:<12} Code", "Stage");
+        for code in codes {
+            println!("{:<12} {}", code.stage, code.code);
+        }
+    }
+
+    let Some(path) = expected else {
+        return Ok(());
+    };
+    let expected = load_expected(path)?;
+    if expected == codes {
+        return Ok(());
+    }
+
+    for code in expected.iter().filter(|code| !codes.contains(code)) {
+        println!("- {:<12} {}", code.stage, code.code);
+    }
+    for code in codes.iter().filter(|code| !expected.contains(code)) {
+        println!("+ {:<12} {}", code.stage, code.code);
+    }
+    Err(SynacorErr::new_plain(ErrorKind::Codes(format!(
+        "The codes differ from those in {}.",
+        path.display()
+    ))))
+}
@@ -0,0 +1,1160 @@
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use crate::cli::FileType;
+use crate::convert::{assemble_lines, u16_to_bin};
+use crate::error::{ErrorKind, SynacorErr, ValueSource};
+use crate::fmt::{render, Line};
+use crate::opcodes::OpName;
+
+// A small structured language compiled to Synacor assembly:
+//
+// let count = 3;
+//
+// fn square(x) { return x * x; }
+//
+// fn main() {
+//     let i = 0;
+//     while (i < count) {
+//         print("square of ", i, " is ", square(i), "\n");
+//         i = i + 1;
+//     }
+// }
+//
+// All values are 15-bit integers. Globals live in memory after the code, while parameters
+// and locals live in a frame on a data stack that grows upwards from the end of the program,
+// so functions can recurse. Expressions are evaluated on the VM stack. Registers:
+//
+// $0 $1  operands and results, $0 also holds return values
+// $2-$5  scratch for prologues, addresses and the runtime routines
+// $6     data stack pointer, the first free cell
+// $7     frame pointer, the cell holding the caller's frame pointer
+//
+// Builtins: `putc(c)` and `getc()` for single characters, `peek(addr)` and
+// `poke(addr, val)` for raw memory, and `halt()`.
+
+const BUILTINS: [(&str, usize); 5] = [
+    ("putc", 1),
+    ("getc", 0),
+    ("peek", 1),
+    ("poke", 2),
+    ("halt", 0),
+];
+
+const KEYWORDS: [&str; 9] = [
+    "fn", "let", "if", "else", "while", "return", "break", "continue", "print",
+];
+
+// ----------------------------------------------------------------------------------------
+// lexer
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Tok {
+    Num(u32),
+    Str(String),
+    Ident(String),
+    Punct(&'static str),
+    Eof,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    tok: Tok,
+    span: Range<usize>,
+}
+
+// longest first, so `==` is not read as two `=`
+const PUNCTUATION: [&str; 24] = [
+    "==", "!=", "<=", ">=", "&&", "||", "+", "-", "*", "/", "%", "&", "|", "~", "!", "<", ">", "=",
+    "(", ")", "{", "}", ",", ";",
+];
+
+struct Source<'a> {
+    path: &'a Path,
+    code: &'a str,
+}
+
+impl Source<'_> {
+    fn err(&self, span: Range<usize>, kind: ErrorKind) -> SynacorErr {
+        SynacorErr::new_code(
+            span.start,
+            span.end,
+            self.path.to_path_buf(),
+            self.code.to_string(),
+            kind,
+        )
+    }
+
+    fn expected(&self, token: &Token, expected: &'static str) -> SynacorErr {
+        let lexeme = match &token.tok {
+            Tok::Eof => "end of file".to_string(),
+            _ => self.code[token.span.clone()].to_string(),
+        };
+        self.err(token.span.clone(), ErrorKind::Parse { lexeme, expected })
+    }
+
+    fn escape(&self, c: char, at: usize) -> Result<char, SynacorErr> {
+        match c {
+            'n' => Ok('\n'),
+            't' => Ok('\t'),
+            '0' => Ok('\0'),
+            '\\' | '"' | '\'' => Ok(c),
+            _ => Err(self.err(
+                at..at + 1 + c.len_utf8(),
+                ErrorKind::Parse {
+                    lexeme: format!("\\{}", c),
+                    expected: "an escape sequence",
+                },
+            )),
+        }
+    }
+
+    fn lex(&self) -> Result<Vec<Token>, SynacorErr> {
+        let code = self.code;
+        let mut tokens = Vec::new();
+        let mut chars = code.char_indices().peekable();
+
+        while let Some(&(start, c)) = chars.peek() {
+            if c.is_whitespace() {
+                chars.next();
+            } else if code[start..].starts_with("//") {
+                while chars.next_if(|(_, c)| *c != '\n').is_some() {}
+            } else if c.is_ascii_digit() {
+                let mut end = start;
+                while let Some((i, c)) = chars.next_if(|(_, c)| c.is_ascii_alphanumeric()) {
+                    end = i + c.len_utf8();
+                }
+                let text = &code[start..end];
+                let parsed = match text.strip_prefix("0x") {
+                    Some(hex) => u32::from_str_radix(hex, 16),
+                    None => text.parse(),
+                };
+                match parsed {
+                    Ok(val) => tokens.push(Token {
+                        tok: Tok::Num(val),
+                        span: start..end,
+                    }),
+                    Err(_) => {
+                        return Err(self.err(
+                            start..end,
+                            ErrorKind::Parse {
+                                lexeme: text.to_string(),
+                                expected: "a number",
+                            },
+                        ))
+                    }
+                }
+            } else if c.is_alphabetic() || c == '_' {
+                let mut end = start;
+                while let Some((i, c)) = chars.next_if(|(_, c)| c.is_alphanumeric() || *c == '_') {
+                    end = i + c.len_utf8();
+                }
+                tokens.push(Token {
+                    tok: Tok::Ident(code[start..end].to_string()),
+                    span: start..end,
+                });
+            } else if c == '"' || c == '\'' {
+                chars.next();
+                let mut text = String::new();
+                let end = loop {
+                    match chars.next() {
+                        Some((i, q)) if q == c => break i + 1,
+                        Some((i, '\\')) => match chars.next() {
+                            Some((_, e)) => text.push(self.escape(e, i)?),
+                            None => break code.len(),
+                        },
+                        Some((_, ch)) => text.push(ch),
+                        None => {
+                            return Err(self.err(
+                                start..code.len(),
+                                ErrorKind::Parse {
+                                    lexeme: code[start..].to_string(),
+                                    expected: "a closed quote",
+                                },
+                            ))
+                        }
+                    }
+                };
+
+                let tok = if c == '"' {
+                    Tok::Str(text)
+                } else {
+                    let mut it = text.chars();
+                    match (it.next(), it.next()) {
+                        (Some(ch), None) => Tok::Num(ch as u32),
+                        _ => {
+                            return Err(self.err(
+                                start..end,
+                                ErrorKind::Parse {
+                                    lexeme: code[start..end].to_string(),
+                                    expected: "a single character",
+                                },
+                            ))
+                        }
+                    }
+                };
+                tokens.push(Token {
+                    tok,
+                    span: start..end,
+                });
+            } else {
+                match PUNCTUATION.iter().find(|p| code[start..].starts_with(**p)) {
+                    Some(p) => {
+                        for _ in 0..p.len() {
+                            chars.next();
+                        }
+                        tokens.push(Token {
+                            tok: Tok::Punct(p),
+                            span: start..start + p.len(),
+                        });
+                    }
+                    None => {
+                        return Err(self.err(
+                            start..start + c.len_utf8(),
+                            ErrorKind::Parse {
+                                lexeme: c.to_string(),
+                                expected: "a token",
+                            },
+                        ))
+                    }
+                }
+            }
+        }
+
+        tokens.push(Token {
+            tok: Tok::Eof,
+            span: code.len()..code.len(),
+        });
+        Ok(tokens)
+    }
+}
+
+// ----------------------------------------------------------------------------------------
+// syntax
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Num(u16),
+    Var(String, Range<usize>),
+    Unary(&'static str, Box<Expr>),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>, Range<usize>),
+}
+
+#[derive(Debug, Clone)]
+enum PrintArg {
+    Str(String),
+    Expr(Expr),
+}
+
+#[derive(Debug, Clone)]
+enum Stmt {
+    Let(String, Option<Expr>),
+    Assign(String, Expr, Range<usize>),
+    If(Expr, Vec<Stmt>, Vec<Stmt>),
+    While(Expr, Vec<Stmt>),
+    Return(Option<Expr>),
+    Break(Range<usize>),
+    Continue(Range<usize>),
+    Print(Vec<PrintArg>),
+    Expr(Expr),
+}
+
+#[derive(Debug, Clone)]
+struct Func {
+    name: String,
+    params: Vec<String>,
+    body: Vec<Stmt>,
+    span: Range<usize>,
+}
+
+#[derive(Debug, Clone)]
+struct Global {
+    name: String,
+    init: Option<Expr>,
+    span: Range<usize>,
+}
+
+// binary operators from loosest to tightest binding
+const PRECEDENCE: [&[&str]; 7] = [
+    &["||"],
+    &["&&"],
+    &["==", "!="],
+    &["<", ">", "<=", ">="],
+    &["|"],
+    &["&"],
+    &["+", "-"],
+];
+const FACTOR_OPS: [&str; 3] = ["*", "/", "%"];
+
+struct Parser<'a> {
+    src: &'a Source<'a>,
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos]
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.pos].clone();
+        if token.tok != Tok::Eof {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn is_punct(&self, p: &str) -> bool {
+        matches!(&self.peek().tok, Tok::Punct(q) if *q == p)
+    }
+
+    fn is_keyword(&self, kw: &str) -> bool {
+        matches!(&self.peek().tok, Tok::Ident(name) if name == kw)
+    }
+
+    fn punct(&mut self, p: &'static str, expected: &'static str) -> Result<Token, SynacorErr> {
+        if self.is_punct(p) {
+            Ok(self.next())
+        } else {
+            Err(self.src.expected(self.peek(), expected))
+        }
+    }
+
+    fn ident(&mut self) -> Result<(String, Range<usize>), SynacorErr> {
+        match self.peek().tok.clone() {
+            Tok::Ident(name) if !KEYWORDS.contains(&name.as_str()) => Ok((name, self.next().span)),
+            _ => Err(self.src.expected(self.peek(), "a name")),
+        }
+    }
+
+    fn program(&mut self) -> Result<(Vec<Global>, Vec<Func>), SynacorErr> {
+        let (mut globals, mut funcs) = (Vec::new(), Vec::new());
+
+        while self.peek().tok != Tok::Eof {
+            if self.is_keyword("let") {
+                let start = self.next().span.start;
+                let (name, _) = self.ident()?;
+                let init = self.initializer()?;
+                let end = self.punct(";", "`;`")?.span.end;
+                globals.push(Global {
+                    name,
+                    init,
+                    span: start..end,
+                });
+            } else if self.is_keyword("fn") {
+                let start = self.next().span.start;
+                let (name, name_span) = self.ident()?;
+                self.punct("(", "`(`")?;
+                let mut params = Vec::new();
+                while !self.is_punct(")") {
+                    params.push(self.ident()?.0);
+                    if !self.is_punct(")") {
+                        self.punct(",", "`,` or `)`")?;
+                    }
+                }
+                self.next();
+                let body = self.block()?;
+                funcs.push(Func {
+                    name,
+                    params,
+                    body,
+                    span: start..name_span.end,
+                });
+            } else {
+                return Err(self.src.expected(self.peek(), "`fn` or `let`"));
+            }
+        }
+
+        Ok((globals, funcs))
+    }
+
+    fn initializer(&mut self) -> Result<Option<Expr>, SynacorErr> {
+        if self.is_punct("=") {
+            self.next();
+            Ok(Some(self.expr()?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn block(&mut self) -> Result<Vec<Stmt>, SynacorErr> {
+        self.punct("{", "`{`")?;
+        let mut stmts = Vec::new();
+        while !self.is_punct("}") {
+            if self.peek().tok == Tok::Eof {
+                return Err(self.src.expected(self.peek(), "`}`"));
+            }
+            stmts.push(self.stmt()?);
+        }
+        self.next();
+        Ok(stmts)
+    }
+
+    fn condition(&mut self) -> Result<Expr, SynacorErr> {
+        self.punct("(", "`(`")?;
+        let cond = self.expr()?;
+        self.punct(")", "`)`")?;
+        Ok(cond)
+    }
+
+    fn stmt(&mut self) -> Result<Stmt, SynacorErr> {
+        let Tok::Ident(word) = self.peek().tok.clone() else {
+            let expr = self.expr()?;
+            self.punct(";", "`;`")?;
+            return Ok(Stmt::Expr(expr));
+        };
+
+        let stmt = match word.as_str() {
+            "let" => {
+                self.next();
+                let (name, _) = self.ident()?;
+                Stmt::Let(name, self.initializer()?)
+            }
+            "if" => {
+                self.next();
+                let cond = self.condition()?;
+                let then = self.block()?;
+                let otherwise = if self.is_keyword("else") {
+                    self.next();
+                    if self.is_keyword("if") {
+                        vec![self.stmt()?]
+                    } else {
+                        self.block()?
+                    }
+                } else {
+                    Vec::new()
+                };
+                return Ok(Stmt::If(cond, then, otherwise));
+            }
+            "while" => {
+                self.next();
+                let cond = self.condition()?;
+                return Ok(Stmt::While(cond, self.block()?));
+            }
+            "return" => {
+                self.next();
+                if self.is_punct(";") {
+                    Stmt::Return(None)
+                } else {
+                    Stmt::Return(Some(self.expr()?))
+                }
+            }
+            "break" => Stmt::Break(self.next().span),
+            "continue" => Stmt::Continue(self.next().span),
+            "print" => {
+                self.next();
+                self.punct("(", "`(`")?;
+                let mut args = Vec::new();
+                while !self.is_punct(")") {
+                    match self.peek().tok.clone() {
+                        Tok::Str(text) => {
+                            let token = self.next();
+                            if let Some(c) = text.chars().find(|c| !c.is_ascii()) {
+                                return Err(self.src.err(
+                                    token.span,
+                                    ErrorKind::Compile(format!(
+                                        "'{}' is not ASCII, so it cannot be printed.",
+                                        c
+                                    )),
+                                ));
+                            }
+                            args.push(PrintArg::Str(text));
+                        }
+                        _ => args.push(PrintArg::Expr(self.expr()?)),
+                    }
+                    if !self.is_punct(")") {
+                        self.punct(",", "`,` or `)`")?;
+                    }
+                }
+                self.next();
+                Stmt::Print(args)
+            }
+            _ if matches!(self.tokens[self.pos + 1].tok, Tok::Punct("=")) => {
+                let (name, span) = self.ident()?;
+                self.next();
+                Stmt::Assign(name, self.expr()?, span)
+            }
+            _ => Stmt::Expr(self.expr()?),
+        };
+
+        self.punct(";", "`;`")?;
+        Ok(stmt)
+    }
+
+    fn expr(&mut self) -> Result<Expr, SynacorErr> {
+        self.binary(0)
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Expr, SynacorErr> {
+        let Some(ops) = PRECEDENCE.get(level) else {
+            return self.factor();
+        };
+
+        let mut lhs = self.binary(level + 1)?;
+        while let Tok::Punct(op) = self.peek().tok {
+            if !ops.contains(&op) {
+                break;
+            }
+            self.next();
+            let rhs = self.binary(level + 1)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn factor(&mut self) -> Result<Expr, SynacorErr> {
+        let mut lhs = self.unary()?;
+        while let Tok::Punct(op) = self.peek().tok {
+            if !FACTOR_OPS.contains(&op) {
+                break;
+            }
+            self.next();
+            let rhs = self.unary()?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr, SynacorErr> {
+        match self.peek().tok {
+            Tok::Punct(op @ ("-" | "!" | "~")) => {
+                self.next();
+                Ok(Expr::Unary(op, Box::new(self.unary()?)))
+            }
+            _ => self.primary(),
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, SynacorErr> {
+        let token = self.peek().clone();
+        match token.tok {
+            Tok::Num(val) => {
+                self.next();
+                if val >= 32768 {
+                    return Err(self.src.err(
+                        token.span,
+                        ErrorKind::InvalidValue(
+                            val.min(u16::MAX as u32) as u16,
+                            ValueSource::Source,
+                        ),
+                    ));
+                }
+                Ok(Expr::Num(val as u16))
+            }
+            Tok::Punct("(") => {
+                self.next();
+                let expr = self.expr()?;
+                self.punct(")", "`)`")?;
+                Ok(expr)
+            }
+            Tok::Ident(_) => {
+                let (name, span) = self.ident()?;
+                if !self.is_punct("(") {
+                    return Ok(Expr::Var(name, span));
+                }
+                self.next();
+                let mut args = Vec::new();
+                while !self.is_punct(")") {
+                    args.push(self.expr()?);
+                    if !self.is_punct(")") {
+                        self.punct(",", "`,` or `)`")?;
+                    }
+                }
+                let end = self.next().span.end;
+                Ok(Expr::Call(name, args, span.start..end))
+            }
+            _ => Err(self.src.expected(&token, "an expression")),
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------------------
+// code generation
+
+#[derive(Debug, Clone)]
+pub(crate) enum Arg {
+    Lit(u16),
+    Reg(u16),
+    Label(String),
+}
+
+/// Instructions with symbolic jump targets, laid out by `resolve`
+#[derive(Debug, Clone)]
+pub(crate) enum Asm {
+    Label(String),
+    Ins(OpName, Vec<Arg>),
+    Data(u16),
+}
+
+use Arg::{Label as L, Lit, Reg};
+
+#[derive(Debug, Clone, Copy)]
+enum Var {
+    Global,
+    /// Offset from the frame pointer
+    Local(u16),
+}
+
+struct Gen<'a> {
+    src: &'a Source<'a>,
+    code: Vec<Asm>,
+    /// Arity of every function
+    funcs: HashMap<String, usize>,
+    globals: Vec<String>,
+    scopes: Vec<HashMap<String, u16>>,
+    /// Frame cells used so far by the current function, after the saved frame pointer
+    slots: u16,
+    /// Continue and break labels of the enclosing loops
+    loops: Vec<(String, String)>,
+    labels: usize,
+    uses_div: bool,
+    uses_print: bool,
+}
+
+impl Gen<'_> {
+    fn ins(&mut self, opname: OpName, args: Vec<Arg>) {
+        self.code.push(Asm::Ins(opname, args));
+    }
+
+    fn label(&mut self) -> String {
+        self.labels += 1;
+        format!(".L{}", self.labels)
+    }
+
+    fn place(&mut self, label: &str) {
+        self.code.push(Asm::Label(label.to_string()));
+    }
+
+    fn lookup(&self, name: &str, span: &Range<usize>) -> Result<Var, SynacorErr> {
+        if let Some(offset) = self.scopes.iter().rev().find_map(|scope| scope.get(name)) {
+            Ok(Var::Local(*offset))
+        } else if self.globals.iter().any(|global| global == name) {
+            Ok(Var::Global)
+        } else {
+            Err(self.src.err(
+                span.clone(),
+                ErrorKind::Compile(format!("`{}` is not defined.", name)),
+            ))
+        }
+    }
+
+    fn declare(&mut self, name: &str) -> u16 {
+        self.slots += 1;
+        let offset = self.slots;
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), offset);
+        }
+        offset
+    }
+
+    // pop the top of the VM stack into a variable
+    fn store(&mut self, var: Var, name: &str) {
+        self.ins(OpName::Pop, vec![Reg(0)]);
+        match var {
+            Var::Global => self.ins(OpName::Wmem, vec![L(name.to_string()), Reg(0)]),
+            Var::Local(offset) => {
+                self.ins(OpName::Add, vec![Reg(5), Reg(7), Lit(offset)]);
+                self.ins(OpName::Wmem, vec![Reg(5), Reg(0)]);
+            }
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) -> Result<(), SynacorErr> {
+        match expr {
+            Expr::Num(val) => self.ins(OpName::Push, vec![Lit(*val)]),
+            Expr::Var(name, span) => {
+                match self.lookup(name, span)? {
+                    Var::Global => self.ins(OpName::Rmem, vec![Reg(0), L(name.clone())]),
+                    Var::Local(offset) => {
+                        self.ins(OpName::Add, vec![Reg(5), Reg(7), Lit(offset)]);
+                        self.ins(OpName::Rmem, vec![Reg(0), Reg(5)]);
+                    }
+                }
+                self.ins(OpName::Push, vec![Reg(0)]);
+            }
+            Expr::Unary(op, inner) => {
+                self.expr(inner)?;
+                self.ins(OpName::Pop, vec![Reg(0)]);
+                match *op {
+                    "-" => {
+                        self.ins(OpName::Not, vec![Reg(0), Reg(0)]);
+                        self.ins(OpName::Add, vec![Reg(0), Reg(0), Lit(1)]);
+                    }
+                    "!" => self.ins(OpName::Eq, vec![Reg(0), Reg(0), Lit(0)]),
+                    _ => self.ins(OpName::Not, vec![Reg(0), Reg(0)]),
+                }
+                self.ins(OpName::Push, vec![Reg(0)]);
+            }
+            Expr::Binary(op @ ("&&" | "||"), lhs, rhs) => {
+                // short circuit, leaving 0 or 1
+                let (decided, end) = (self.label(), self.label());
+                let jump = if *op == "&&" { OpName::Jf } else { OpName::Jt };
+                for side in [lhs, rhs] {
+                    self.expr(side)?;
+                    self.ins(OpName::Pop, vec![Reg(0)]);
+                    self.ins(jump, vec![Reg(0), L(decided.clone())]);
+                }
+                let (fallthrough, shortcut) = if *op == "&&" { (1, 0) } else { (0, 1) };
+                self.ins(OpName::Push, vec![Lit(fallthrough)]);
+                self.ins(OpName::Jmp, vec![L(end.clone())]);
+                self.place(&decided);
+                self.ins(OpName::Push, vec![Lit(shortcut)]);
+                self.place(&end);
+            }
+            Expr::Binary(op, lhs, rhs) => {
+                self.expr(lhs)?;
+                self.expr(rhs)?;
+                self.ins(OpName::Pop, vec![Reg(1)]);
+                self.ins(OpName::Pop, vec![Reg(0)]);
+                let (a, b) = (Reg(0), Reg(1));
+                match *op {
+                    "+" => self.ins(OpName::Add, vec![Reg(0), a, b]),
+                    "*" => self.ins(OpName::Mult, vec![Reg(0), a, b]),
+                    "%" => self.ins(OpName::Mod, vec![Reg(0), a, b]),
+                    "&" => self.ins(OpName::And, vec![Reg(0), a, b]),
+                    "|" => self.ins(OpName::Or, vec![Reg(0), a, b]),
+                    "==" => self.ins(OpName::Eq, vec![Reg(0), a, b]),
+                    ">" => self.ins(OpName::Gt, vec![Reg(0), a, b]),
+                    "<" => self.ins(OpName::Gt, vec![Reg(0), b, a]),
+                    "!=" | "<=" | ">=" => {
+                        let test = match *op {
+                            "!=" => vec![Reg(0), a, b],
+                            "<=" => vec![Reg(0), a, b],
+                            _ => vec![Reg(0), b, a],
+                        };
+                        let opname = if *op == "!=" { OpName::Eq } else { OpName::Gt };
+                        self.ins(opname, test);
+                        self.ins(OpName::Eq, vec![Reg(0), Reg(0), Lit(0)]);
+                    }
+                    "-" => {
+                        self.ins(OpName::Not, vec![Reg(1), Reg(1)]);
+                        self.ins(OpName::Add, vec![Reg(1), Reg(1), Lit(1)]);
+                        self.ins(OpName::Add, vec![Reg(0), a, b]);
+                    }
+                    _ => {
+                        self.uses_div = true;
+                        self.ins(OpName::Call, vec![L("__div".to_string())]);
+                    }
+                }
+                self.ins(OpName::Push, vec![Reg(0)]);
+            }
+            Expr::Call(name, args, span) => self.call(name, args, span)?,
+        }
+        Ok(())
+    }
+
+    fn call(&mut self, name: &str, args: &[Expr], span: &Range<usize>) -> Result<(), SynacorErr> {
+        let arity = BUILTINS
+            .iter()
+            .find(|(builtin, _)| *builtin == name)
+            .map(|(_, arity)| *arity)
+            .or_else(|| self.funcs.get(name).copied());
+
+        match arity {
+            Some(arity) if arity == args.len() => (),
+            Some(arity) => {
+                return Err(self.src.err(
+                    span.clone(),
+                    ErrorKind::Compile(format!(
+                        "`{}` takes {} arguments but is given {}.",
+                        name,
+                        arity,
+                        args.len()
+                    )),
+                ))
+            }
+            None => {
+                return Err(self.src.err(
+                    span.clone(),
+                    ErrorKind::Compile(format!("There is no function `{}`.", name)),
+                ))
+            }
+        }
+
+        for arg in args {
+            self.expr(arg)?;
+        }
+
+        match name {
+            "putc" => {
+                self.ins(OpName::Pop, vec![Reg(0)]);
+                self.ins(OpName::Out, vec![Reg(0)]);
+            }
+            "getc" => self.ins(OpName::In, vec![Reg(0)]),
+            "peek" => {
+                self.ins(OpName::Pop, vec![Reg(0)]);
+                self.ins(OpName::Rmem, vec![Reg(0), Reg(0)]);
+            }
+            "poke" => {
+                self.ins(OpName::Pop, vec![Reg(1)]);
+                self.ins(OpName::Pop, vec![Reg(0)]);
+                self.ins(OpName::Wmem, vec![Reg(0), Reg(1)]);
+            }
+            "halt" => self.ins(OpName::Halt, vec![]),
+            _ => self.ins(OpName::Call, vec![L(name.to_string())]),
+        }
+        // every call leaves a value, builtins without one leave whatever $0 holds
+        self.ins(OpName::Push, vec![Reg(0)]);
+        Ok(())
+    }
+
+    fn block(&mut self, stmts: &[Stmt]) -> Result<(), SynacorErr> {
+        self.scopes.push(HashMap::new());
+        for stmt in stmts {
+            self.stmt(stmt)?;
+        }
+        self.scopes.pop();
+        Ok(())
+    }
+
+    fn epilogue(&mut self) {
+        self.ins(OpName::Pop, vec![Reg(0)]);
+        self.ins(OpName::Set, vec![Reg(6), Reg(7)]);
+        self.ins(OpName::Rmem, vec![Reg(7), Reg(7)]);
+        self.ins(OpName::Ret, vec![]);
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) -> Result<(), SynacorErr> {
+        match stmt {
+            Stmt::Let(name, init) => {
+                // the variable is not in scope in its own initializer
+                match init {
+                    Some(init) => self.expr(init)?,
+                    None => self.ins(OpName::Push, vec![Lit(0)]),
+                }
+                let offset = self.declare(name);
+                self.store(Var::Local(offset), name);
+            }
+            Stmt::Assign(name, value, span) => {
+                let var = self.lookup(name, span)?;
+                self.expr(value)?;
+                self.store(var, name);
+            }
+            Stmt::If(cond, then, otherwise) => {
+                let (other, end) = (self.label(), self.label());
+                self.expr(cond)?;
+                self.ins(OpName::Pop, vec![Reg(0)]);
+                self.ins(OpName::Jf, vec![Reg(0), L(other.clone())]);
+                self.block(then)?;
+                self.ins(OpName::Jmp, vec![L(end.clone())]);
+                self.place(&other);
+                self.block(otherwise)?;
+                self.place(&end);
+            }
+            Stmt::While(cond, body) => {
+                let (top, end) = (self.label(), self.label());
+                self.place(&top);
+                self.expr(cond)?;
+                self.ins(OpName::Pop, vec![Reg(0)]);
+                self.ins(OpName::Jf, vec![Reg(0), L(end.clone())]);
+                self.loops.push((top.clone(), end.clone()));
+                self.block(body)?;
+                self.loops.pop();
+                self.ins(OpName::Jmp, vec![L(top)]);
+                self.place(&end);
+            }
+            Stmt::Return(value) => {
+                match value {
+                    Some(value) => self.expr(value)?,
+                    None => self.ins(OpName::Push, vec![Lit(0)]),
+                }
+                self.epilogue();
+            }
+            Stmt::Break(span) | Stmt::Continue(span) => {
+                let Some((top, end)) = self.loops.last().cloned() else {
+                    return Err(self.src.err(
+                        span.clone(),
+                        ErrorKind::Compile("`break` and `continue` only work in loops.".into()),
+                    ));
+                };
+                let to = if matches!(stmt, Stmt::Break(_)) {
+                    end
+                } else {
+                    top
+                };
+                self.ins(OpName::Jmp, vec![L(to)]);
+            }
+            Stmt::Print(args) => {
+                for arg in args {
+                    match arg {
+                        PrintArg::Str(text) => {
+                            for c in text.chars() {
+                                self.ins(OpName::Out, vec![Lit(c as u16)]);
+                            }
+                        }
+                        PrintArg::Expr(expr) => {
+                            self.expr(expr)?;
+                            self.ins(OpName::Pop, vec![Reg(0)]);
+                            self.uses_print = true;
+                            self.uses_div = true;
+                            self.ins(OpName::Call, vec![L("__print".to_string())]);
+                        }
+                    }
+                }
+            }
+            Stmt::Expr(expr) => {
+                self.expr(expr)?;
+                self.ins(OpName::Pop, vec![Reg(0)]);
+            }
+        }
+        Ok(())
+    }
+
+    fn function(&mut self, func: &Func) -> Result<(), SynacorErr> {
+        self.slots = 0;
+        self.scopes = vec![HashMap::new()];
+        for param in func.params.iter() {
+            self.declare(param);
+        }
+
+        let outer = std::mem::take(&mut self.code);
+        self.block(&func.body)?;
+        // falling off the end returns 0
+        self.ins(OpName::Push, vec![Lit(0)]);
+        self.epilogue();
+        let body = std::mem::replace(&mut self.code, outer);
+
+        // the caller pushed the arguments before the return address
+        self.place(&func.name);
+        self.ins(OpName::Pop, vec![Reg(2)]);
+        self.ins(OpName::Wmem, vec![Reg(6), Reg(7)]);
+        self.ins(OpName::Set, vec![Reg(7), Reg(6)]);
+        self.ins(OpName::Add, vec![Reg(6), Reg(6), Lit(self.slots + 1)]);
+        for i in (0..func.params.len() as u16).rev() {
+            self.ins(OpName::Pop, vec![Reg(0)]);
+            self.ins(OpName::Add, vec![Reg(5), Reg(7), Lit(i + 1)]);
+            self.ins(OpName::Wmem, vec![Reg(5), Reg(0)]);
+        }
+        self.ins(OpName::Push, vec![Reg(2)]);
+        self.code.extend(body);
+        Ok(())
+    }
+
+    // `$0 / $1` into `$0`, by subtracting the largest doubling of the divisor that fits
+    fn runtime_div(&mut self) {
+        use OpName::*;
+        let routine = [
+            Asm::Label("__div".into()),
+            // faults on a zero divisor, just like `%`
+            Asm::Ins(Mod, vec![Reg(4), Reg(0), Reg(1)]),
+            Asm::Ins(Set, vec![Reg(2), Lit(0)]),
+            Asm::Label("__div_loop".into()),
+            Asm::Ins(Gt, vec![Reg(3), Reg(1), Reg(0)]),
+            Asm::Ins(Jt, vec![Reg(3), L("__div_done".into())]),
+            Asm::Ins(Set, vec![Reg(3), Reg(1)]),
+            Asm::Ins(Set, vec![Reg(4), Lit(1)]),
+            Asm::Label("__div_double".into()),
+            Asm::Ins(Gt, vec![Reg(5), Reg(3), Lit(0x3fff)]),
+            Asm::Ins(Jt, vec![Reg(5), L("__div_sub".into())]),
+            Asm::Ins(Add, vec![Reg(5), Reg(3), Reg(3)]),
+            Asm::Ins(Gt, vec![Reg(5), Reg(5), Reg(0)]),
+            Asm::Ins(Jt, vec![Reg(5), L("__div_sub".into())]),
+            Asm::Ins(Add, vec![Reg(3), Reg(3), Reg(3)]),
+            Asm::Ins(Add, vec![Reg(4), Reg(4), Reg(4)]),
+            Asm::Ins(Jmp, vec![L("__div_double".into())]),
+            Asm::Label("__div_sub".into()),
+            Asm::Ins(Not, vec![Reg(3), Reg(3)]),
+            Asm::Ins(Add, vec![Reg(3), Reg(3), Lit(1)]),
+            Asm::Ins(Add, vec![Reg(0), Reg(0), Reg(3)]),
+            Asm::Ins(Add, vec![Reg(2), Reg(2), Reg(4)]),
+            Asm::Ins(Jmp, vec![L("__div_loop".into())]),
+            Asm::Label("__div_done".into()),
+            Asm::Ins(Set, vec![Reg(0), Reg(2)]),
+            Asm::Ins(Ret, vec![]),
+        ];
+        self.code.extend(routine);
+    }
+
+    // `$0` in decimal, digits are pushed above a 10 that marks the end
+    fn runtime_print(&mut self) {
+        use OpName::*;
+        let routine = [
+            Asm::Label("__print".into()),
+            Asm::Ins(Push, vec![Lit(10)]),
+            Asm::Label("__print_digit".into()),
+            Asm::Ins(Mod, vec![Reg(1), Reg(0), Lit(10)]),
+            Asm::Ins(Push, vec![Reg(1)]),
+            Asm::Ins(Set, vec![Reg(1), Lit(10)]),
+            Asm::Ins(Call, vec![L("__div".into())]),
+            Asm::Ins(Jt, vec![Reg(0), L("__print_digit".into())]),
+            Asm::Label("__print_out".into()),
+            Asm::Ins(Pop, vec![Reg(0)]),
+            Asm::Ins(Eq, vec![Reg(1), Reg(0), Lit(10)]),
+            Asm::Ins(Jt, vec![Reg(1), L("__print_done".into())]),
+            Asm::Ins(Add, vec![Reg(0), Reg(0), Lit(b'0' as u16)]),
+            Asm::Ins(Out, vec![Reg(0)]),
+            Asm::Ins(Jmp, vec![L("__print_out".into())]),
+            Asm::Label("__print_done".into()),
+            Asm::Ins(Ret, vec![]),
+        ];
+        self.code.extend(routine);
+    }
+}
+
+fn check_names(
+    src: &Source,
+    globals: &[Global],
+    funcs: &[Func],
+) -> Result<HashMap<String, usize>, SynacorErr> {
+    let mut seen: HashMap<&str, &Range<usize>> = HashMap::new();
+    let names = globals
+        .iter()
+        .map(|global| (&global.name, &global.span))
+        .chain(funcs.iter().map(|func| (&func.name, &func.span)));
+
+    for (name, span) in names {
+        let reserved =
+            name.starts_with("__") || BUILTINS.iter().any(|(builtin, _)| builtin == name);
+        if reserved || seen.insert(name, span).is_some() {
+            return Err(src.err(
+                span.clone(),
+                ErrorKind::Compile(format!("`{}` is already defined.", name)),
+            ));
+        }
+    }
+
+    match funcs.iter().find(|func| func.name == "main") {
+        Some(main) if !main.params.is_empty() => Err(src.err(
+            main.span.clone(),
+            ErrorKind::Compile("`main` takes no arguments.".to_string()),
+        )),
+        Some(_) => Ok(funcs
+            .iter()
+            .map(|func| (func.name.clone(), func.params.len()))
+            .collect()),
+        None => Err(src.err(
+            0..0,
+            ErrorKind::Compile("There is no `main` function.".to_string()),
+        )),
+    }
+}
+
+// addresses of the labels, then the program as assembly lines commented with label names
+pub(crate) fn resolve(code: &[Asm]) -> Vec<Line> {
+    let mut addrs: BTreeMap<&str, usize> = BTreeMap::new();
+    let mut addr = 0;
+    for item in code {
+        match item {
+            Asm::Label(label) => {
+                addrs.insert(label, addr);
+            }
+            Asm::Ins(_, args) => addr += args.len() + 1,
+            Asm::Data(_) => addr += 1,
+        }
+    }
+
+    let mut lines = Vec::new();
+    let mut names: Vec<&str> = Vec::new();
+    let mut addr = 0;
+    for item in code {
+        let lexemes = match item {
+            Asm::Label(label) => {
+                names.push(label);
+                continue;
+            }
+            Asm::Ins(opname, args) => [opname.to_string()]
+                .into_iter()
+                .chain(args.iter().map(|arg| match arg {
+                    Lit(val) => format!("{:#06x}", val),
+                    Reg(reg) => format!("${}", reg),
+                    L(label) => format!("{:#06x}", addrs[label.as_str()]),
+                }))
+                .collect(),
+            Asm::Data(val) => vec!["data".to_string(), format!("{:#06x}", val)],
+        };
+
+        let size = lexemes.len() - (matches!(item, Asm::Data(_)) as usize);
+        lines.push(Line {
+            addr: Some(addr),
+            lexemes,
+            comment: (!names.is_empty()).then(|| format!("; {}", names.join(", "))),
+        });
+        names.clear();
+        addr += size;
+    }
+
+    lines
+}
+
+/// Compile a program to canonical assembly
+pub fn compile(code: &str, path: &Path) -> Result<String, SynacorErr> {
+    let src = Source { path, code };
+    let tokens = src.lex()?;
+    let (globals, funcs) = Parser {
+        src: &src,
+        tokens,
+        pos: 0,
+    }
+    .program()?;
+
+    let mut gen = Gen {
+        src: &src,
+        code: Vec::new(),
+        funcs: check_names(&src, &globals, &funcs)?,
+        globals: Vec::new(),
+        scopes: Vec::new(),
+        slots: 0,
+        loops: Vec::new(),
+        labels: 0,
+        uses_div: false,
+        uses_print: false,
+    };
+
+    // start up: the data stack goes after everything else, then globals in order, then main
+    gen.ins(OpName::Set, vec![Reg(6), L("__stack".to_string())]);
+    for global in globals.iter() {
+        match &global.init {
+            Some(init) => {
+                gen.expr(init)?;
+                gen.globals.push(global.name.clone());
+                gen.store(Var::Global, &global.name);
+            }
+            None => gen.globals.push(global.name.clone()),
+        }
+    }
+    gen.ins(OpName::Call, vec![L("main".to_string())]);
+    gen.ins(OpName::Halt, vec![]);
+
+    for func in funcs.iter() {
+        gen.function(func)?;
+    }
+    if gen.uses_print {
+        gen.runtime_print();
+    }
+    if gen.uses_div {
+        gen.runtime_div();
+    }
+
+    for global in globals.iter() {
+        gen.place(&global.name);
+        gen.code.push(Asm::Data(0));
+    }
+    gen.place("__stack");
+    gen.code.push(Asm::Data(0));
+
+    Ok(render(&resolve(&gen.code)))
+}
+
+/// Compile a source file to assembly or to a binary
+pub fn compile_file(path: &PathBuf, out_path: &PathBuf, emit: &FileType) -> Result<(), SynacorErr> {
+    let code = std::fs::read_to_string(path)?;
+    write_program(&compile(&code, path)?, out_path, emit)
+}
+
+/// Write assembly as is, or assembled into a binary
+pub fn write_program(asm: &str, out_path: &PathBuf, emit: &FileType) -> Result<(), SynacorErr> {
+    match emit {
+        FileType::Assembly => {
+            std::fs::write(out_path, asm)?;
+            println!("Created assembly file {}", out_path.display());
+        }
+        FileType::Binary => {
+            let memory = assemble_lines(asm, out_path)?.concat();
+            u16_to_bin(memory, out_path)?;
+        }
+    }
+    Ok(())
+}
@@ -2,10 +2,13 @@ use itertools::Itertools;
 use std::fs::File;
 use std::io::Write;
 use std::num::ParseIntError;
-use std::path::PathBuf;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 
-use crate::error::SynacorErr;
+use crate::error::{ErrorKind, SynacorErr, ValueSource};
+use crate::fmt::{render, Line};
 use crate::opcodes::{ASM_CONVERT, INS_WIDTH};
+use crate::xref::Xrefs;
 
 pub fn bin_to_u16(path: &PathBuf) -> Result<Vec<u16>, SynacorErr> {
     let bytes = std::fs::read(path)?;
@@ -25,26 +28,86 @@ pub fn u16_to_bin(memory: Vec<u16>, out_path: &PathBuf) -> Result<(), SynacorErr
     Ok(())
 }
 
-// Line addresses are optional!
+// Line addresses are optional! Everything after a `;` is a comment.
 
 pub fn asm_to_u16(path: &PathBuf) -> Result<Vec<u16>, SynacorErr> {
     let asm = std::fs::read_to_string(path)?;
+    Ok(assemble_lines(&asm, path)?.concat())
+}
+
+/// Byte span in the assembly source of every value in memory
+#[derive(Debug, Clone)]
+pub struct SourceMap {
+    pub file: PathBuf,
+    pub code: String,
+    pub spans: Vec<Range<usize>>,
+}
+
+impl SourceMap {
+    /// Span covering `len` values starting at `addr`
+    pub fn span(&self, addr: usize, len: usize) -> Option<Range<usize>> {
+        let first = self.spans.get(addr)?;
+        let last = self.spans.get(addr + len.max(1) - 1).unwrap_or(first);
+        Some(first.start..last.end)
+    }
+}
+
+pub fn asm_to_u16_with_map(path: &PathBuf) -> Result<(Vec<u16>, SourceMap), SynacorErr> {
+    let asm = std::fs::read_to_string(path)?;
+    assemble_with_map(asm, path)
+}
+
+// as `asm_to_u16_with_map`, for source that is not read from disk
+pub fn assemble_with_map(asm: String, path: &Path) -> Result<(Vec<u16>, SourceMap), SynacorErr> {
+    let (memory, spans) = assemble_spanned(&asm, path)?.into_iter().flatten().unzip();
+
+    let map = SourceMap {
+        file: path.to_path_buf(),
+        code: asm,
+        spans,
+    };
+    Ok((memory, map))
+}
+
+// values for each line of `asm`, with any errors pointing into `path`
+pub fn assemble_lines(asm: &str, path: &Path) -> Result<Vec<Vec<u16>>, SynacorErr> {
+    Ok(assemble_spanned(asm, path)?
+        .into_iter()
+        .map(|line| line.into_iter().map(|(val, _)| val).collect())
+        .collect())
+}
+
+// lexemes of a line and their byte offsets in `asm`, leaving out addresses, comments and `data`
+fn lexemes<'a>(asm: &str, line: &'a str) -> Vec<(Range<usize>, &'a str)> {
+    let comment_strip = match line.split_once(";") {
+        Some((head, _)) => head,
+        None => line,
+    };
+
+    let address_strip = match comment_strip.split_once(":") {
+        Some((_, tail)) => tail,
+        None => comment_strip,
+    };
+
+    address_strip
+        .split_whitespace()
+        .filter(|lexeme| lexeme != &"data")
+        .map(|lexeme| {
+            let start = lexeme.as_ptr() as usize - asm.as_ptr() as usize;
+            (start..start + lexeme.len(), lexeme)
+        })
+        .collect()
+}
+
+// an assembled value with the span of the lexeme it came from
+type Spanned = (u16, Range<usize>);
 
+fn assemble_spanned(asm: &str, path: &Path) -> Result<Vec<Vec<Spanned>>, SynacorErr> {
     asm.lines()
-        .enumerate()
-        .flat_map(|(line_num, line)| {
-            let address_strip = match line.split_once(":") {
-                Some((_, tail)) => tail,
-                None => line,
-            };
-
-            address_strip
-                .split_whitespace()
-                .collect::<Vec<&str>>()
-                .iter()
-                .filter(|lexeme| lexeme != &&"data")
-                .enumerate()
-                .map(|(line_idx, lexeme)| {
+        .map(|line| {
+            lexemes(asm, line)
+                .into_iter()
+                .map(|(span, lexeme)| {
                     let lookup_or_parse: Result<u16, ParseIntError> =
                         match ASM_CONVERT.get_by_right(lexeme) {
                             Some(int) => Ok(*int),
@@ -54,112 +117,83 @@ pub fn asm_to_u16(path: &PathBuf) -> Result<Vec<u16>, SynacorErr> {
                             }
                         };
 
-                    match lookup_or_parse {
-                        Ok(bin) => {
-                            if bin >= 32776 {
-                                let (start, end) = char_range(&asm, line_num, line_idx);
-                                Err(SynacorErr::new_code(
-                                    start,
-                                    end,
-                                    path.to_path_buf(),
-                                    asm.to_string(),
-                                    format!("Value {} is invalid.", bin),
-                                ))
-                            } else {
-                                Ok(bin)
-                            }
-                        }
-                        Err(_) => {
-                            let (start, end) = char_range(&asm, line_num, line_idx);
-                            Err(SynacorErr::new_code(
-                                start,
-                                end,
-                                path.to_path_buf(),
-                                asm.to_string(),
-                                format!("\"{}\" does not parse as a valid u16.", lexeme),
-                            ))
-                        }
-                    }
+                    let kind = match lookup_or_parse {
+                        Ok(bin) if bin < 32776 => return Ok((bin, span)),
+                        Ok(bin) => ErrorKind::InvalidValue(bin, ValueSource::Source),
+                        Err(_) => ErrorKind::Parse {
+                            lexeme: lexeme.to_string(),
+                            expected: "a valid u16",
+                        },
+                    };
+
+                    Err(SynacorErr::new_code(
+                        span.start,
+                        span.end,
+                        path.to_path_buf(),
+                        asm.to_string(),
+                        kind,
+                    ))
                 })
-                .collect::<Vec<Result<u16, SynacorErr>>>()
+                .collect::<Result<Vec<Spanned>, SynacorErr>>()
         })
         .collect()
 }
 
-// TODO check this... not working in challenge.asm in middle, maybe because of data filter or
-// address numbers
-
-fn char_range(asm: &String, idx: usize, line_idx: usize) -> (usize, usize) {
-    let splits: Vec<Vec<&str>> = asm
-        .lines()
-        .map(|line| line.split_whitespace().collect::<Vec<&str>>())
-        .collect();
-    let mem_before = &splits[idx][..line_idx];
-    let chars_before = mem_before.len() + mem_before.iter().fold(0, |acc, x| acc + x.len());
-    let current = &splits[idx][line_idx].len();
-    (chars_before, chars_before + current)
-}
-
-pub fn u16_to_asm(memory: Vec<u16>, out_path: &PathBuf) -> Result<(), SynacorErr> {
-    let mut res: Vec<Result<String, SynacorErr>> = Vec::new();
+/// Listing of a program in the canonical assembly format, with xref comments
+pub fn u16_to_lines(memory: &[u16]) -> Result<Vec<Line>, SynacorErr> {
+    let xrefs = Xrefs::new(memory);
+    let mut lines: Vec<Line> = Vec::new();
     let mut it = memory.iter().enumerate();
 
     while let Some((addr, val)) = it.next() {
-        let line: Result<String, SynacorErr> = if let (Some(opcode_str), Some(width)) =
+        let lexemes: Vec<String> = if let (Some(opcode_str), Some(width)) =
             (ASM_CONVERT.get_by_left(val), INS_WIDTH.get(val))
         {
-            let lexemes: Vec<(usize, u16)> = it
+            let operands: Vec<String> = it
                 .by_ref()
                 .take(*width)
-                .map(|(addr, val)| (addr, *val))
-                .collect();
-
-            let res_operands: Result<Vec<String>, SynacorErr> = lexemes
-                .iter()
                 .map(|(addr, val)| {
                     if val >= &32768 {
                         if let Some(register) = ASM_CONVERT.get_by_left(val) {
                             Ok(register.to_string())
                         } else {
                             Err(SynacorErr::new_addr(
-                                *addr,
-                                format!("Invalid value {}.", val),
+                                addr,
+                                ErrorKind::InvalidValue(*val, ValueSource::Memory),
                             ))
                         }
                     } else {
                         Ok(format!("{:#06x}", val))
                     }
                 })
-                .collect();
-
-            match res_operands {
-                Err(e) => Err(e),
-                Ok(operands) => Ok(
-                    vec![vec![format!("{:#06x}:{}", addr, opcode_str)], operands]
-                        .iter()
-                        .flatten()
-                        .join(" "),
-                ),
-            }
+                .collect::<Result<Vec<String>, SynacorErr>>()?;
+
+            [vec![opcode_str.to_string()], operands].concat()
         } else {
-            Ok(format!("{:#06x}:data {:#06x}", addr, val))
+            vec!["data".to_string(), format!("{:#06x}", val)]
         };
-        res.push(line);
-    }
 
-    // report first Err
-
-    if let Some(Err(e)) = res.iter().find(|line| line.is_err()) {
-        Err(e.clone())
-    } else {
-        let full_asm = res
-            .into_iter()
-            .flatten()
-            .collect::<Vec<String>>()
-            .join("\n");
-        let mut file = File::create(out_path)?;
-        write!(file, "{}", full_asm)?;
-        println!("Created assembly file {}", out_path.display());
-        Ok(())
+        lines.push(Line {
+            addr: Some(addr),
+            lexemes,
+            comment: xrefs.comment(addr),
+        });
     }
+
+    Ok(lines)
+}
+
+/// Source map into the disassembly of a binary, so it can be reported on like assembly
+pub fn disassembly_map(memory: &[u16], path: &Path) -> Result<SourceMap, SynacorErr> {
+    let asm = render(&u16_to_lines(memory)?);
+    let (_, map) = assemble_with_map(asm, path)?;
+    Ok(map)
+}
+
+pub fn u16_to_asm(memory: Vec<u16>, out_path: &PathBuf) -> Result<(), SynacorErr> {
+    let lines = u16_to_lines(&memory)?;
+    let mut file = File::create(out_path)?;
+    write!(file, "{}", render(&lines))?;
+    println!("Created assembly file {}", out_path.display());
+    Ok(())
 }
@@ -0,0 +1,648 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as FmtWrite;
+
+use itertools::Itertools;
+
+use crate::cfg::{BasicBlock, Cfg, EdgeKind, Function};
+use crate::disasm::{Instruction, Operand};
+use crate::opcodes::OpName;
+
+// stands in for the single exit node when computing post-dominators
+const EXIT: usize = usize::MAX;
+
+fn expr(operand: &Operand) -> String {
+    match operand {
+        Operand::Register(reg) => format!("r{}", reg),
+        _ => operand.to_string(),
+    }
+}
+
+fn escape(c: char) -> String {
+    match c {
+        '\n' => "\\n".to_string(),
+        '"' => "\\\"".to_string(),
+        '\\' => "\\\\".to_string(),
+        _ => c.to_string(),
+    }
+}
+
+fn negate(cond: String) -> String {
+    match cond.strip_prefix('!') {
+        Some(inner) => inner.to_string(),
+        None => format!("!{}", cond),
+    }
+}
+
+// condition under which a `jt` or `jf` takes its jump
+fn condition(ins: &Instruction) -> String {
+    let tested = expr(&ins.operands[0]);
+    match ins.opname {
+        OpName::Jf => negate(tested),
+        _ => tested,
+    }
+}
+
+/// Registers read and written by an instruction, ignoring calls
+fn uses_defs(ins: &Instruction) -> (Vec<u16>, Option<u16>) {
+    let dest = ins.dest();
+    let reads = ins
+        .operands
+        .iter()
+        .skip(dest.is_some() as usize)
+        .filter_map(|operand| match operand {
+            Operand::Register(reg) => Some(*reg),
+            _ => None,
+        })
+        .collect();
+    (reads, dest)
+}
+
+fn statement(ins: &Instruction, target: Option<usize>, args: &[u16]) -> Option<String> {
+    let ops: Vec<String> = ins.operands.iter().map(expr).collect();
+
+    let line = match ins.opname {
+        OpName::Halt => "halt();".to_string(),
+        OpName::Set => format!("{} = {};", ops[0], ops[1]),
+        OpName::Push => format!("push({});", ops[0]),
+        OpName::Pop => format!("{} = pop();", ops[0]),
+        OpName::Not => format!("{} = ~{};", ops[0], ops[1]),
+        OpName::Rmem => format!("{} = mem[{}];", ops[0], ops[1]),
+        OpName::Wmem => format!("mem[{}] = {};", ops[0], ops[1]),
+        OpName::Call => match target {
+            Some(target) => format!(
+                "fn_{:#06x}({});",
+                target,
+                args.iter().map(|reg| format!("r{}", reg)).join(", ")
+            ),
+            None => format!("call({});", ops[0]),
+        },
+        OpName::Ret => "return;".to_string(),
+        OpName::Out => format!("out({});", ops[0]),
+        OpName::In => format!("{} = getc();", ops[0]),
+        OpName::Noop | OpName::Jmp | OpName::Jt | OpName::Jf => return None,
+        _ => {
+            let operator = ins.opname.operator()?;
+            let folded = match (ins.operands[1], ins.operands[2]) {
+                (Operand::Literal(b), Operand::Literal(c)) => ins.opname.eval(b, c),
+                _ => None,
+            };
+            match folded {
+                Some(val) => format!(
+                    "{} = {:#06x}; // {} {} {}",
+                    ops[0], val, ops[1], operator, ops[2]
+                ),
+                None => format!("{} = {} {} {};", ops[0], ops[1], operator, ops[2]),
+            }
+        }
+    };
+
+    Some(line)
+}
+
+enum Line {
+    Label(usize),
+    Code(usize, String),
+}
+
+struct Loop {
+    header: usize,
+    exit: Option<usize>,
+    body: BTreeSet<usize>,
+}
+
+struct Decompiler<'a> {
+    function: &'a Function,
+    // parameters of every function, by entry, for the arguments of calls
+    params: BTreeMap<usize, BTreeSet<u16>>,
+    saved: Vec<u16>,
+    ipdom: BTreeMap<usize, usize>,
+    loops: BTreeMap<usize, BTreeSet<usize>>,
+    // callee-saved pushes and pops, shown once in the signature instead
+    hidden: BTreeSet<usize>,
+    emitted: BTreeSet<usize>,
+    gotos: BTreeSet<usize>,
+    lines: Vec<Line>,
+}
+
+impl<'a> Decompiler<'a> {
+    fn new(function: &'a Function) -> Self {
+        let mut decompiler = Self {
+            function,
+            params: BTreeMap::new(),
+            saved: Vec::new(),
+            ipdom: BTreeMap::new(),
+            loops: BTreeMap::new(),
+            hidden: BTreeSet::new(),
+            emitted: BTreeSet::new(),
+            gotos: BTreeSet::new(),
+            lines: Vec::new(),
+        };
+        decompiler.post_dominators();
+        decompiler.natural_loops();
+        decompiler.saved = decompiler.callee_saved();
+        decompiler
+    }
+
+    fn args(&self, ins: &Instruction) -> Vec<u16> {
+        self.function
+            .targets
+            .get(&ins.addr)
+            .and_then(|target| self.params.get(target))
+            .map(|params| params.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    fn successors(&self, block: usize) -> Vec<usize> {
+        self.function.blocks[&block]
+            .successors
+            .iter()
+            .map(|edge| edge.target)
+            .filter(|target| self.function.blocks.contains_key(target))
+            .collect()
+    }
+
+    fn post_dominators(&mut self) {
+        let all: BTreeSet<usize> = self.function.blocks.keys().copied().chain([EXIT]).collect();
+
+        let mut pdom: BTreeMap<usize, BTreeSet<usize>> =
+            all.iter().map(|&block| (block, all.clone())).collect();
+        pdom.insert(EXIT, BTreeSet::from([EXIT]));
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &block in self.function.blocks.keys().rev() {
+                let mut successors = self.successors(block);
+                if successors.is_empty() {
+                    successors.push(EXIT);
+                }
+
+                let mut new = successors
+                    .iter()
+                    .map(|succ| pdom[succ].clone())
+                    .reduce(|acc, set| &acc & &set)
+                    .unwrap_or_default();
+                new.insert(block);
+
+                if new != pdom[&block] {
+                    pdom.insert(block, new);
+                    changed = true;
+                }
+            }
+        }
+
+        for (&block, set) in pdom.iter() {
+            // the immediate post-dominator is the strict one closest to the block
+            if let Some(&ipdom) = set
+                .iter()
+                .find(|&&other| other != block && pdom[&other].len() == set.len() - 1)
+            {
+                if ipdom != EXIT {
+                    self.ipdom.insert(block, ipdom);
+                }
+            }
+        }
+    }
+
+    fn natural_loops(&mut self) {
+        // depth first search for back edges, which target a block still on the path
+        let mut on_path = BTreeSet::new();
+        let mut visited = BTreeSet::new();
+        let mut back_edges = Vec::new();
+        let mut stack = vec![(self.function.entry, 0)];
+
+        while let Some((block, idx)) = stack.pop() {
+            if idx == 0 {
+                visited.insert(block);
+                on_path.insert(block);
+            }
+            let successors = self.successors(block);
+            match successors.get(idx) {
+                Some(&succ) => {
+                    stack.push((block, idx + 1));
+                    if on_path.contains(&succ) {
+                        back_edges.push((block, succ));
+                    } else if !visited.contains(&succ) {
+                        stack.push((succ, 0));
+                    }
+                }
+                None => {
+                    on_path.remove(&block);
+                }
+            }
+        }
+
+        let mut predecessors: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+        for &block in self.function.blocks.keys() {
+            for succ in self.successors(block) {
+                predecessors.entry(succ).or_default().push(block);
+            }
+        }
+
+        for (latch, header) in back_edges {
+            let body = self.loops.entry(header).or_default();
+            body.insert(header);
+            let mut todo = vec![latch];
+            while let Some(block) = todo.pop() {
+                if body.insert(block) {
+                    todo.extend(predecessors.get(&block).into_iter().flatten());
+                }
+            }
+        }
+    }
+
+    // number of blocks reachable from `start`, used to size up branches
+    fn reach(&self, start: usize) -> usize {
+        let mut seen = BTreeSet::new();
+        let mut todo = vec![start];
+        while let Some(block) = todo.pop() {
+            if self.function.blocks.contains_key(&block) && seen.insert(block) {
+                todo.extend(self.successors(block));
+            }
+        }
+        seen.len()
+    }
+
+    fn line(&mut self, depth: usize, code: String) {
+        self.lines.push(Line::Code(depth, code));
+    }
+
+    fn emit_region(
+        &mut self,
+        start: usize,
+        follow: Option<usize>,
+        ctx: Option<&Loop>,
+        depth: usize,
+    ) {
+        let mut cur = Some(start);
+
+        while let Some(block) = cur {
+            if Some(block) == follow {
+                break;
+            }
+
+            if let Some(ctx) = ctx {
+                if block == ctx.header {
+                    self.line(depth, "continue;".to_string());
+                    break;
+                }
+                if Some(block) == ctx.exit {
+                    self.line(depth, "break;".to_string());
+                    break;
+                }
+            }
+
+            if self.emitted.contains(&block) || !self.function.blocks.contains_key(&block) {
+                self.gotos.insert(block);
+                self.line(depth, format!("goto L_{:#06x};", block));
+                break;
+            }
+
+            cur = if self.loops.contains_key(&block) {
+                self.emit_loop(block, depth)
+            } else {
+                self.emit_block(block, ctx, depth)
+            };
+        }
+    }
+
+    fn emit_loop(&mut self, header: usize, depth: usize) -> Option<usize> {
+        let body = self.loops[&header].clone();
+        let exit = body
+            .iter()
+            .flat_map(|&block| self.successors(block))
+            .filter(|succ| !body.contains(succ))
+            .min();
+        let ctx = Loop { header, exit, body };
+
+        if let Some((cond, inside)) = self.loop_condition(&ctx) {
+            self.emitted.insert(header);
+            self.lines.push(Line::Label(header));
+            self.line(depth, format!("while ({}) {{", cond));
+            self.emit_region(inside, Some(header), Some(&ctx), depth + 1);
+            self.line(depth, "}".to_string());
+            return exit;
+        }
+
+        self.line(depth, "while (true) {".to_string());
+        if let Some(next) = self.emit_block(header, Some(&ctx), depth + 1) {
+            self.emit_region(next, None, Some(&ctx), depth + 1);
+        }
+        self.line(depth, "}".to_string());
+
+        exit
+    }
+
+    // the condition for staying in a loop whose header does nothing but test it, and the
+    // block the loop goes on to
+    fn loop_condition(&self, ctx: &Loop) -> Option<(String, usize)> {
+        let block = &self.function.blocks[&ctx.header];
+        let last = block.last();
+        let shown = block
+            .instructions
+            .iter()
+            .filter(|ins| !self.hidden.contains(&ins.addr))
+            .count();
+        if !matches!(last.opname, OpName::Jt | OpName::Jf) || shown != 1 {
+            return None;
+        }
+
+        let edge = |kind: EdgeKind| {
+            block
+                .successors
+                .iter()
+                .find(|edge| edge.kind == kind)
+                .map(|edge| edge.target)
+        };
+        let (taken, not_taken) = (edge(EdgeKind::Taken)?, edge(EdgeKind::NotTaken)?);
+        let cond = condition(last);
+        if Some(taken) == ctx.exit && ctx.body.contains(&not_taken) {
+            Some((negate(cond), not_taken))
+        } else if Some(not_taken) == ctx.exit && ctx.body.contains(&taken) {
+            Some((cond, taken))
+        } else {
+            None
+        }
+    }
+
+    // emits a single block, plus both arms of a closing branch, and returns where to continue
+    fn emit_block(&mut self, start: usize, ctx: Option<&Loop>, depth: usize) -> Option<usize> {
+        let block = &self.function.blocks[&start];
+        self.emitted.insert(start);
+        self.lines.push(Line::Label(start));
+
+        let mut text = String::new();
+        for ins in block.instructions.iter() {
+            if self.hidden.contains(&ins.addr) {
+                continue;
+            }
+
+            // runs of literal characters are shown as a single print
+            if ins.opname == OpName::Out {
+                if let Some(Ok(ascii)) = ins.operands[0].literal().map(u8::try_from) {
+                    text.push_str(&escape(ascii as char));
+                    continue;
+                }
+            }
+            if !text.is_empty() {
+                self.line(depth, format!("print(\"{}\");", text));
+                text.clear();
+            }
+
+            let target = self.function.targets.get(&ins.addr).copied();
+            if let Some(stmt) = statement(ins, target, &self.args(ins)) {
+                self.line(depth, stmt);
+            }
+        }
+        if !text.is_empty() {
+            self.line(depth, format!("print(\"{}\");", text));
+        }
+
+        let last = block.last().clone();
+        let edge = |kind: EdgeKind| {
+            block
+                .successors
+                .iter()
+                .find(|edge| edge.kind == kind)
+                .map(|edge| edge.target)
+        };
+
+        match last.opname {
+            OpName::Jmp => {
+                let target = edge(EdgeKind::Jump);
+                if target.is_none() {
+                    self.line(depth, format!("goto *{};", expr(&last.operands[0])));
+                }
+                target
+            }
+            OpName::Jt | OpName::Jf => {
+                let not_taken = edge(EdgeKind::NotTaken);
+                let Some(taken) = edge(EdgeKind::Taken) else {
+                    self.line(
+                        depth,
+                        format!(
+                            "if ({}) goto *{};",
+                            condition(&last),
+                            expr(&last.operands[1])
+                        ),
+                    );
+                    return not_taken;
+                };
+
+                let mut join = self.ipdom.get(&start).copied();
+                // a join outside the loop is reached through break instead
+                if let (Some(ctx), Some(block)) = (ctx, join) {
+                    if !ctx.body.contains(&block) && Some(block) != ctx.exit {
+                        join = None;
+                    }
+                }
+
+                let cond = condition(&last);
+                let (cond, then, other) = if Some(taken) == join {
+                    (negate(cond), not_taken, None)
+                } else if not_taken == join {
+                    (cond, Some(taken), None)
+                } else {
+                    (cond, Some(taken), not_taken)
+                };
+
+                // arms that never meet again are flattened, with the smaller one as a guard
+                if let (None, Some(then), Some(other)) = (join, then, other) {
+                    let (cond, guard, rest) = if self.reach(then) <= self.reach(other) {
+                        (cond, then, other)
+                    } else {
+                        (negate(cond), other, then)
+                    };
+                    self.line(depth, format!("if ({}) {{", cond));
+                    self.emit_region(guard, None, ctx, depth + 1);
+                    self.line(depth, "}".to_string());
+                    return Some(rest);
+                }
+
+                if let Some(then) = then {
+                    self.line(depth, format!("if ({}) {{", cond));
+                    self.emit_region(then, join, ctx, depth + 1);
+                    if let Some(other) = other {
+                        self.line(depth, "} else {".to_string());
+                        self.emit_region(other, join, ctx, depth + 1);
+                    }
+                    self.line(depth, "}".to_string());
+                }
+
+                join
+            }
+            OpName::Halt | OpName::Ret => None,
+            _ => edge(EdgeKind::Fallthrough),
+        }
+    }
+
+    // pushes at the entry that are popped in reverse right before every return
+    fn callee_saved(&mut self) -> Vec<u16> {
+        let entry = &self.function.blocks[&self.function.entry];
+        let prologue: Vec<&Instruction> = entry
+            .instructions
+            .iter()
+            .take_while(|ins| {
+                ins.opname == OpName::Push && matches!(ins.operands[0], Operand::Register(_))
+            })
+            .collect();
+        let saved: Vec<Operand> = prologue.iter().map(|ins| ins.operands[0]).collect();
+
+        let mut hidden: BTreeSet<usize> = prologue.iter().map(|ins| ins.addr).collect();
+        let returns: Vec<&BasicBlock> = self
+            .function
+            .blocks
+            .values()
+            .filter(|block| block.last().opname == OpName::Ret)
+            .collect();
+        if returns.is_empty() {
+            return Vec::new();
+        }
+
+        for block in returns {
+            let epilogue: Vec<&Instruction> = block
+                .instructions
+                .iter()
+                .rev()
+                .skip(1)
+                .take(saved.len())
+                .collect();
+            let matches = epilogue.len() == saved.len()
+                && epilogue
+                    .iter()
+                    .zip(saved.iter())
+                    .all(|(ins, reg)| ins.opname == OpName::Pop && ins.operands[0] == *reg);
+            if !matches {
+                return Vec::new();
+            }
+            hidden.extend(epilogue.iter().map(|ins| ins.addr));
+        }
+
+        self.hidden = hidden;
+        saved
+            .iter()
+            .filter_map(|operand| match operand {
+                Operand::Register(reg) => Some(*reg),
+                _ => None,
+            })
+            .collect()
+    }
+
+    // registers live on entry, found by backwards liveness over the blocks
+    fn parameters(&self) -> BTreeSet<u16> {
+        let mut uses: BTreeMap<usize, BTreeSet<u16>> = BTreeMap::new();
+        let mut defs: BTreeMap<usize, BTreeSet<u16>> = BTreeMap::new();
+
+        for (&start, block) in self.function.blocks.iter() {
+            let (used, defined) = (
+                uses.entry(start).or_default(),
+                defs.entry(start).or_default(),
+            );
+            for ins in block.instructions.iter() {
+                if self.hidden.contains(&ins.addr) {
+                    continue;
+                }
+                let (mut reads, dest) = uses_defs(ins);
+                reads.extend(self.args(ins));
+                used.extend(reads.into_iter().filter(|reg| !defined.contains(reg)));
+                defined.extend(dest);
+            }
+        }
+
+        let mut live_in: BTreeMap<usize, BTreeSet<u16>> = BTreeMap::new();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &start in self.function.blocks.keys().rev() {
+                let live_out: BTreeSet<u16> = self
+                    .successors(start)
+                    .iter()
+                    .flat_map(|succ| live_in.get(succ).into_iter().flatten().copied())
+                    .collect();
+                let new: BTreeSet<u16> = uses[&start]
+                    .union(&(&live_out - &defs[&start]))
+                    .copied()
+                    .collect();
+                if live_in.get(&start) != Some(&new) {
+                    live_in.insert(start, new);
+                    changed = true;
+                }
+            }
+        }
+
+        live_in.remove(&self.function.entry).unwrap_or_default()
+    }
+
+    fn render(mut self) -> String {
+        let saved = std::mem::take(&mut self.saved);
+        let params = self.parameters();
+
+        self.emit_region(self.function.entry, None, None, 1);
+
+        let mut out = String::new();
+        if !saved.is_empty() {
+            let _ = writeln!(
+                out,
+                "// saves {}",
+                saved.iter().map(|reg| format!("r{}", reg)).join(", ")
+            );
+        }
+        let _ = writeln!(
+            out,
+            "{}({}) {{",
+            self.function.name(),
+            params.iter().map(|reg| format!("r{}", reg)).join(", ")
+        );
+        for line in self.lines.iter() {
+            match line {
+                Line::Label(block) if self.gotos.contains(block) => {
+                    let _ = writeln!(out, "L_{:#06x}:", block);
+                }
+                Line::Label(_) => (),
+                Line::Code(depth, code) => {
+                    let _ = writeln!(out, "{}{}", "    ".repeat(*depth), code);
+                }
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+}
+
+/// C-like pseudo-code for a single function on its own, where the arguments of calls are
+/// left out as other functions are not analysed; `decompile` fills them in
+pub fn decompile_function(function: &Function) -> String {
+    Decompiler::new(function).render()
+}
+
+/// Pseudo-code for every function in the program, or only the one starting at `entry`
+pub fn decompile(memory: &[u16], entry: Option<usize>) -> String {
+    let cfg = match entry {
+        Some(entry) => Cfg::with_entries(memory, &[entry]),
+        None => Cfg::new(memory),
+    };
+    let mut decompilers: Vec<Decompiler> = cfg.functions.values().map(Decompiler::new).collect();
+
+    // arguments of calls are read by the caller too, so parameters grow until they settle
+    loop {
+        let params: BTreeMap<usize, BTreeSet<u16>> = decompilers
+            .iter()
+            .map(|decompiler| (decompiler.function.entry, decompiler.parameters()))
+            .collect();
+        if decompilers
+            .iter()
+            .all(|decompiler| decompiler.params == params)
+        {
+            break;
+        }
+        for decompiler in decompilers.iter_mut() {
+            decompiler.params = params.clone();
+        }
+    }
+
+    decompilers
+        .into_iter()
+        .filter(|decompiler| entry.is_none() || entry == Some(decompiler.function.entry))
+        .map(Decompiler::render)
+        .join("\n")
+}
@@ -0,0 +1,157 @@
+use std::collections::BTreeMap;
+use std::fmt::Write as FmtWrite;
+use std::ops::Range;
+use std::path::PathBuf;
+
+use itertools::Itertools;
+
+use crate::cfg::Cfg;
+use crate::cli::FileType;
+use crate::convert::{asm_to_u16, bin_to_u16};
+use crate::disasm::Instruction;
+use crate::error::SynacorErr;
+use crate::snapshot::Snapshot;
+use crate::strings::changed_ranges;
+
+/// Either side of a diff, a program as loaded from disk or a saved VM state
+#[derive(Debug, Clone)]
+pub enum Image {
+    Program(Vec<u16>),
+    Snapshot(Snapshot),
+}
+
+impl Image {
+    // binaries are checked for the snapshot header, so either kind can be passed
+    pub fn read(path: &PathBuf, ftype: &FileType) -> Result<Self, SynacorErr> {
+        match ftype {
+            FileType::Assembly => Ok(Self::Program(asm_to_u16(path)?)),
+            FileType::Binary => {
+                if Snapshot::is_snapshot(&std::fs::read(path)?) {
+                    Ok(Self::Snapshot(Snapshot::load(path)?))
+                } else {
+                    Ok(Self::Program(bin_to_u16(path)?))
+                }
+            }
+        }
+    }
+
+    pub fn memory(&self) -> &[u16] {
+        match self {
+            Self::Program(memory) => memory,
+            Self::Snapshot(snapshot) => &snapshot.memory,
+        }
+    }
+
+    // reachable instructions by address, everything else counts as data
+    fn code(&self, len: usize) -> BTreeMap<usize, Instruction> {
+        let mut memory = self.memory().to_vec();
+        memory.resize(len, 0);
+
+        let entries = match self {
+            Self::Program(_) => vec![0],
+            Self::Snapshot(snapshot) => vec![0, snapshot.addr],
+        };
+
+        Cfg::with_entries(&memory, &entries)
+            .functions
+            .values()
+            .flat_map(|function| function.blocks.values())
+            .flat_map(|block| block.instructions.iter())
+            .map(|ins| (ins.addr, ins.clone()))
+            .collect()
+    }
+}
+
+fn overlapping(code: &BTreeMap<usize, Instruction>, range: &Range<usize>) -> Vec<Instruction> {
+    code.values()
+        .filter(|ins| ins.addr < range.end && ins.next_addr() > range.start)
+        .cloned()
+        .collect()
+}
+
+fn diff_memory(out: &mut String, a: &Image, b: &Image) {
+    let len = a.memory().len().max(b.memory().len());
+    let (mut mem_a, mut mem_b) = (a.memory().to_vec(), b.memory().to_vec());
+    mem_a.resize(len, 0);
+    mem_b.resize(len, 0);
+
+    let (code_a, code_b) = (a.code(len), b.code(len));
+
+    for range in changed_ranges(&mem_a, &mem_b) {
+        let (ins_a, ins_b) = (overlapping(&code_a, &range), overlapping(&code_b, &range));
+
+        if !ins_a.is_empty() || !ins_b.is_empty() {
+            let _ = writeln!(out, "@@ {:#06x}..{:#06x} code @@", range.start, range.end);
+            for ins in ins_a.iter() {
+                let _ = writeln!(out, "- {:#06x}: {}", ins.addr, ins);
+            }
+            for ins in ins_b.iter() {
+                let _ = writeln!(out, "+ {:#06x}: {}", ins.addr, ins);
+            }
+        } else {
+            let _ = writeln!(
+                out,
+                "@@ {:#06x}..{:#06x} data, {} values changed @@",
+                range.start,
+                range.end,
+                range.len()
+            );
+            // short edits are shown in full, long ones are usually decryption
+            if range.len() <= 8 {
+                let _ = writeln!(
+                    out,
+                    "- {}\n+ {}",
+                    mem_a[range.clone()]
+                        .iter()
+                        .map(|val| format!("{:#06x}", val))
+                        .join(" "),
+                    mem_b[range.clone()]
+                        .iter()
+                        .map(|val| format!("{:#06x}", val))
+                        .join(" ")
+                );
+            }
+        }
+    }
+}
+
+fn diff_state(out: &mut String, a: &Snapshot, b: &Snapshot) {
+    if a.addr != b.addr {
+        let _ = writeln!(out, "address: {:#06x} -> {:#06x}", a.addr, b.addr);
+    }
+
+    for (reg, (val_a, val_b)) in a.registers.iter().zip(b.registers.iter()).enumerate() {
+        if val_a != val_b {
+            let _ = writeln!(out, "${}: {:#06x} -> {:#06x}", reg, val_a, val_b);
+        }
+    }
+
+    if a.stack != b.stack {
+        let common = a
+            .stack
+            .iter()
+            .zip(b.stack.iter())
+            .take_while(|(x, y)| x == y)
+            .count();
+        let fmt = |stack: &[u16]| stack.iter().map(|val| format!("{:#06x}", val)).join(" ");
+        let _ = writeln!(
+            out,
+            "stack[{}..]: [{}] -> [{}]",
+            common,
+            fmt(&a.stack[common..]),
+            fmt(&b.stack[common..])
+        );
+    }
+}
+
+/// Human readable differences between two images, empty when they are identical
+pub fn diff(a: &Image, b: &Image) -> String {
+    let mut out = String::new();
+
+    if let (Image::Snapshot(a), Image::Snapshot(b)) = (a, b) {
+        diff_state(&mut out, a, b);
+    }
+    diff_memory(&mut out, a, b);
+
+    out
+}
@@ -0,0 +1,142 @@
+use std::fmt;
+
+use crate::opcodes::{OpName, INS_WIDTH};
+
+const BITS_15: u16 = 32768;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    Literal(u16),
+    Register(u16),
+    Invalid(u16),
+}
+
+impl From<u16> for Operand {
+    fn from(val: u16) -> Self {
+        if val < BITS_15 {
+            Self::Literal(val)
+        } else if val < BITS_15 + 8 {
+            Self::Register(val - BITS_15)
+        } else {
+            Self::Invalid(val)
+        }
+    }
+}
+
+impl Operand {
+    pub fn literal(&self) -> Option<u16> {
+        match self {
+            Self::Literal(val) => Some(*val),
+            _ => None,
+        }
+    }
+
+    /// Value as stored in memory
+    pub fn encode(&self) -> u16 {
+        match self {
+            Self::Literal(val) | Self::Invalid(val) => *val,
+            Self::Register(reg) => BITS_15 + reg,
+        }
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Literal(val) | Self::Invalid(val) => write!(f, "{:#06x}", val),
+            Self::Register(reg) => write!(f, "${}", reg),
+        }
+    }
+}
+
+/// A single decoded instruction and the address it was read from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction {
+    pub addr: usize,
+    pub opname: OpName,
+    pub operands: Vec<Operand>,
+}
+
+impl Instruction {
+    /// Decode the instruction at `addr`, or `None` if the opcode is invalid or truncated
+    pub fn decode(memory: &[u16], addr: usize) -> Option<Self> {
+        let opcode_id = *memory.get(addr)?;
+        let opname = OpName::try_from(opcode_id).ok()?;
+        let width = *INS_WIDTH.get(&opcode_id)?;
+
+        let operands = memory
+            .get(addr + 1..addr + 1 + width)?
+            .iter()
+            .map(|val| Operand::from(*val))
+            .collect();
+
+        Some(Self {
+            addr,
+            opname,
+            operands,
+        })
+    }
+
+    /// Number of memory cells taken by the opcode and its operands
+    pub fn size(&self) -> usize {
+        self.operands.len() + 1
+    }
+
+    /// Opcode followed by the operands, as stored in memory
+    pub fn encode(&self) -> Vec<u16> {
+        [self.opname as u16]
+            .into_iter()
+            .chain(self.operands.iter().map(|operand| operand.encode()))
+            .collect()
+    }
+
+    pub fn next_addr(&self) -> usize {
+        self.addr + self.size()
+    }
+
+    /// Operand holding the destination of a jump or call
+    pub fn target_operand(&self) -> Option<Operand> {
+        match self.opname {
+            OpName::Jmp | OpName::Call => Some(self.operands[0]),
+            OpName::Jt | OpName::Jf => Some(self.operands[1]),
+            _ => None,
+        }
+    }
+
+    /// Literal destination of a jump or call, if there is one
+    pub fn target(&self) -> Option<usize> {
+        self.target_operand()?.literal().map(|val| val as usize)
+    }
+
+    /// Register written by the instruction, if any
+    pub fn dest(&self) -> Option<u16> {
+        match self.opname {
+            OpName::Set
+            | OpName::Pop
+            | OpName::Eq
+            | OpName::Gt
+            | OpName::Add
+            | OpName::Mult
+            | OpName::Mod
+            | OpName::And
+            | OpName::Or
+            | OpName::Not
+            | OpName::Rmem
+            | OpName::In => match self.operands[0] {
+                Operand::Register(reg) => Some(reg),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.opname)?;
+        for operand in &self.operands {
+            write!(f, " {}", operand)?;
+        }
+        Ok(())
+    }
+}
@@ -1,11 +1,50 @@
 use std::error::Error;
 use std::fmt;
+use std::io::IsTerminal;
 use std::path::PathBuf;
 
+use clap::ValueEnum;
 use codespan_reporting::diagnostic::{Diagnostic, Label};
 use codespan_reporting::files::SimpleFiles;
 use codespan_reporting::term::emit;
 use codespan_reporting::term::termcolor::{ColorChoice, StandardStream};
+use strum_macros::Display;
+
+use serde_json::{json, Value};
+
+use crate::convert::SourceMap;
+use crate::opcodes::OpName;
+
+/// How errors are reported on stderr
+#[derive(ValueEnum, Display, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[strum(serialize_all = "lowercase")]
+pub enum MessageFormat {
+    /// Rendered source snippets, for people
+    #[default]
+    Human,
+    /// One JSON object per line, for editors and CI
+    Json,
+}
+
+#[derive(ValueEnum, Display, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[strum(serialize_all = "lowercase")]
+pub enum ColorWhen {
+    /// Color only when stderr is a terminal
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorWhen {
+    pub fn choice(&self) -> ColorChoice {
+        match self {
+            Self::Auto if std::io::stderr().is_terminal() => ColorChoice::Auto,
+            Self::Auto | Self::Never => ColorChoice::Never,
+            Self::Always => ColorChoice::Always,
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub enum Location {
@@ -17,19 +56,220 @@ pub enum Location {
         code: String,
     },
     IO,
+    /// Nowhere in particular, for failures that are neither in the program nor on I/O
+    None,
+}
+
+/// Where a value that is neither a literal nor a register turned up
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueSource {
+    /// A number written in a source file
+    Source,
+    /// An operand in memory being disassembled
+    Memory,
+    /// An operand read while running
+    Operand,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorKind {
+    InvalidOpcode(u16),
+    /// A value of 32776 or more, which is neither a literal nor a register
+    InvalidValue(u16, ValueSource),
+    InvalidRegister(u16),
+    /// `pop` or `ret` with nothing on the stack
+    StackUnderflow(OpName),
+    MemoryOutOfRange(usize),
+    InvalidAscii(u16),
+    DivisionByZero(u16),
+    Parse {
+        lexeme: String,
+        expected: &'static str,
+    },
+    Patch(String),
+    Snapshot(String),
+    /// `fmt --check` found a file that differs from its formatted form
+    Unformatted(String),
+    /// A program the optimizer cannot safely rewrite
+    Optimize(String),
+    /// A program in the structured language that parses but cannot be compiled
+    Compile(String),
+    /// A replayed session that stopped matching its recording
+    Replay(String),
+    /// stdin ended while the program waited for input
+    EndOfInput,
+    /// A program still running after the number of instructions it was given
+    StepLimit(usize),
+    /// A goal or puzzle the solvers found no answer for
+    Solve(String),
+    /// Harvested codes that differ from the expected ones
+    Codes(String),
+    /// Number of lint warnings raised to errors
+    LintDenied(usize),
+    Io(String),
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidOpcode(opcode) => write!(f, "Opcode {} is not valid.", opcode),
+            Self::InvalidValue(val, ValueSource::Source) => write!(f, "Value {} is invalid.", val),
+            Self::InvalidValue(val, ValueSource::Memory) => write!(f, "Invalid value {}.", val),
+            Self::InvalidValue(val, ValueSource::Operand) => {
+                write!(f, "Value {} falls outside 15-bit range.", val)
+            }
+            Self::InvalidRegister(reg) => {
+                write!(f, "Attempted to access invalid register {}", reg)
+            }
+            Self::StackUnderflow(opname) => write!(f, "{:?} called on an empty stack.", opname),
+            Self::MemoryOutOfRange(addr) => {
+                write!(f, "Attempt to access invalid memory address {}", addr)
+            }
+            Self::InvalidAscii(val) => write!(f, "Invalid ASCII code {}", val),
+            Self::DivisionByZero(val) => write!(f, "Modulo {} by zero.", val),
+            Self::Parse { lexeme, expected } => {
+                write!(f, "\"{}\" does not parse as {}.", lexeme, expected)
+            }
+            Self::Unformatted(file) => {
+                write!(f, "{} is not formatted, run `synacor fmt` to fix it.", file)
+            }
+            Self::EndOfInput => write!(f, "Input ended while the program was waiting for more."),
+            Self::LintDenied(count) => write!(f, "{} denied lint warnings.", count),
+            Self::StepLimit(steps) => write!(
+                f,
+                "The program neither halted nor asked for input within {} steps.",
+                steps
+            ),
+            Self::Patch(details)
+            | Self::Snapshot(details)
+            | Self::Optimize(details)
+            | Self::Compile(details)
+            | Self::Replay(details)
+            | Self::Solve(details)
+            | Self::Codes(details)
+            | Self::Io(details) => {
+                write!(f, "{}", details)
+            }
+        }
+    }
+}
+
+/// VM state captured when execution fails
+#[derive(Debug, Clone)]
+pub struct RuntimeContext {
+    pub addr: usize,
+    /// Disassembly of the faulting instruction
+    pub instruction: String,
+    /// Number of values making up the faulting instruction
+    pub width: usize,
+    pub registers: [u16; 8],
+    pub stack_len: usize,
+    /// Top of the stack, with the most recently pushed value last
+    pub stack_top: Vec<u16>,
+    /// Most recently executed addresses, ending with the faulting one
+    pub history: Vec<usize>,
+}
+
+impl RuntimeContext {
+    pub fn lines(&self) -> Vec<String> {
+        let registers = self
+            .registers
+            .iter()
+            .enumerate()
+            .map(|(reg, val)| format!("${} {:#06x}", reg, val))
+            .collect::<Vec<String>>()
+            .join("  ");
+
+        let stack = if self.stack_len == 0 {
+            "empty".to_string()
+        } else {
+            format!(
+                "{} ({} values, top last)",
+                self.stack_top
+                    .iter()
+                    .map(|val| format!("{:#06x}", val))
+                    .collect::<Vec<String>>()
+                    .join(" "),
+                self.stack_len
+            )
+        };
+
+        let history = self
+            .history
+            .iter()
+            .map(|addr| format!("{:#06x}", addr))
+            .collect::<Vec<String>>()
+            .join(" -> ");
+
+        vec![
+            format!("instruction: {:#06x}: {}", self.addr, self.instruction),
+            format!("registers:   {}", registers),
+            format!("stack:       {}", stack),
+            format!("history:     {}", history),
+        ]
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct SynacorErr {
     pub location: Location,
-    pub details: String,
+    pub kind: ErrorKind,
+    pub context: Option<Box<RuntimeContext>>,
 }
 
 impl SynacorErr {
-    pub fn new_addr(addr: usize, details: String) -> Self {
+    pub fn new_addr(addr: usize, kind: ErrorKind) -> Self {
         Self {
             location: Location::Address(addr),
-            details,
+            kind,
+            context: None,
+        }
+    }
+
+    // errors that belong to a file rather than a place in memory or source
+    pub fn new_io(kind: ErrorKind) -> Self {
+        Self {
+            location: Location::IO,
+            kind,
+            context: None,
+        }
+    }
+
+    // errors that need no location, such as a search that found nothing
+    pub fn new_plain(kind: ErrorKind) -> Self {
+        Self {
+            location: Location::None,
+            kind,
+            context: None,
+        }
+    }
+
+    pub fn with_context(self, context: RuntimeContext) -> Self {
+        Self {
+            context: Some(Box::new(context)),
+            ..self
+        }
+    }
+
+    /// Point a runtime error at the assembly the faulting instruction came from
+    pub fn with_source(self, map: &SourceMap) -> Self {
+        let span = match (&self.location, &self.context) {
+            (Location::Address(_), Some(context)) => map.span(context.addr, context.width),
+            (Location::Address(addr), None) => map.span(*addr, 1),
+            _ => None,
+        };
+
+        match span {
+            Some(span) => Self {
+                location: Location::Code {
+                    start: span.start,
+                    end: span.end,
+                    file: map.file.clone(),
+                    code: map.code.clone(),
+                },
+                ..self
+            },
+            None => self,
         }
     }
 
@@ -38,7 +278,7 @@ impl SynacorErr {
         end: usize,
         file: PathBuf,
         code: String,
-        details: String,
+        kind: ErrorKind,
     ) -> Self {
         Self {
             location: Location::Code {
@@ -47,61 +287,127 @@ impl SynacorErr {
                 file,
                 code,
             },
-            details,
+            kind,
+            context: None,
+        }
+    }
+
+    /// Address in memory the error refers to, if any
+    pub fn addr(&self) -> Option<usize> {
+        match (&self.location, &self.context) {
+            (_, Some(context)) => Some(context.addr),
+            (Location::Address(addr), None) => Some(*addr),
+            _ => None,
         }
     }
 
-    pub fn emit(&self) -> Result<(), codespan_reporting::files::Error> {
-        match &self.location {
+    /// Structured form of the error for `--message-format json`. Lines and columns count
+    /// from one, byte spans are offsets into the file.
+    pub fn to_json(&self) -> Value {
+        let (file, span) = match &self.location {
             Location::Code {
                 start,
                 end,
                 file,
                 code,
-            } => {
-                let mut files = SimpleFiles::new();
+            } => (
+                Value::from(file.to_string_lossy()),
+                span_json(code, *start, *end),
+            ),
+            _ => (Value::Null, Value::Null),
+        };
+
+        json!({
+            "severity": "error",
+            "message": self.kind.to_string(),
+            "file": file,
+            "span": span,
+            "address": self.addr(),
+            "notes": self.context.iter().flat_map(|c| c.lines()).collect::<Vec<String>>(),
+        })
+    }
+
+    pub fn emit(
+        &self,
+        format: MessageFormat,
+        color: ColorChoice,
+    ) -> Result<(), codespan_reporting::files::Error> {
+        if format == MessageFormat::Json {
+            eprintln!("{}", self.to_json());
+        } else if let Location::Code {
+            start,
+            end,
+            file,
+            code,
+        } = &self.location
+        {
+            let mut files = SimpleFiles::new();
 
-                let name = file.to_string_lossy();
-                let file_id = files.add(name, code);
+            let name = file.to_string_lossy();
+            let file_id = files.add(name, code);
 
-                let diagnostic = Diagnostic::error()
-                    .with_message(&self.details)
-                    .with_labels(vec![Label::primary(file_id, *start..*end)]);
+            let diagnostic = Diagnostic::error()
+                .with_message(self.kind.to_string())
+                .with_labels(vec![Label::primary(file_id, *start..*end)])
+                .with_notes(self.context.iter().flat_map(|c| c.lines()).collect());
 
-                let writer = StandardStream::stderr(ColorChoice::Always);
-                let config = codespan_reporting::term::Config::default();
+            let writer = StandardStream::stderr(color);
+            let config = codespan_reporting::term::Config::default();
 
-                emit(&mut writer.lock(), &config, &files, &diagnostic)?;
+            emit(&mut writer.lock(), &config, &files, &diagnostic)?;
+        } else {
+            eprintln!("{}", self);
+            if let Some(context) = &self.context {
+                eprintln!();
+                for line in context.lines() {
+                    eprintln!("{}", line);
+                }
             }
-            _ => (),
         }
         Ok(())
     }
 }
 
+// one-based line and column, in characters, of a byte offset
+fn line_col(code: &str, offset: usize) -> (usize, usize) {
+    let before = &code[..offset.min(code.len())];
+    let line_start = before.rfind('\n').map_or(0, |pos| pos + 1);
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+// byte span plus the lines and columns it covers, as reported in JSON diagnostics
+pub(crate) fn span_json(code: &str, start: usize, end: usize) -> Value {
+    let (start_line, start_column) = line_col(code, start);
+    let (end_line, end_column) = line_col(code, end);
+    json!({
+        "start": start,
+        "end": end,
+        "start_line": start_line,
+        "start_column": start_column,
+        "end_line": end_line,
+        "end_column": end_column,
+    })
+}
+
 impl fmt::Display for SynacorErr {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self.location {
             Location::Address(addr) => {
-                write!(f, "Failure at address {}.\n\n{}", addr, self.details)
+                write!(f, "Failure at address {}.\n\n{}", addr, self.kind)
             }
-            Location::Code { .. } => write!(f, "{}", self.details),
-            Location::IO => write!(f, "IO error: {}", self.details),
+            Location::Code { .. } | Location::None => write!(f, "{}", self.kind),
+            Location::IO => write!(f, "IO error: {}", self.kind),
         }
     }
 }
 
-impl Error for SynacorErr {
-    fn description(&self) -> &str {
-        &self.details
-    }
-}
+impl Error for SynacorErr {}
 
 impl From<std::io::Error> for SynacorErr {
     fn from(e: std::io::Error) -> Self {
-        SynacorErr {
-            location: Location::IO,
-            details: format!("{}", e),
-        }
+        SynacorErr::new_io(ErrorKind::Io(format!("{}", e)))
     }
 }
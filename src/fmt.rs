@@ -0,0 +1,272 @@
+use std::path::Path;
+
+use crate::convert::assemble_lines;
+use crate::error::{ErrorKind, SynacorErr};
+use crate::opcodes::ASM_CONVERT;
+
+// Canonical assembly: a `0x0000: ` address on every line when any line has one, mnemonics
+// in a four character column, operands in six character columns, every literal as
+// four-digit hex, and comments lined up after the longest instruction.
+//
+// 0x0000: add  $0     $1     0x0004 ; xref jump 0x0009
+// 0x0004: out  $0
+
+const MNEMONIC_WIDTH: usize = 4;
+const OPERAND_WIDTH: usize = 6;
+
+/// One line of assembly split into the parts the formatter lines up
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Line {
+    pub addr: Option<usize>,
+    /// Mnemonics, operands and `data`, with literals already in canonical form
+    pub lexemes: Vec<String>,
+    /// Comment text starting at the `;`
+    pub comment: Option<String>,
+}
+
+impl Line {
+    fn code(&self, addresses: bool) -> String {
+        let prefix = match self.addr {
+            Some(addr) => format!("{:#06x}: ", addr),
+            None if addresses && !self.lexemes.is_empty() => " ".repeat(8),
+            None => String::new(),
+        };
+
+        let body = self
+            .lexemes
+            .iter()
+            .enumerate()
+            .map(|(i, lexeme)| match i {
+                0 => format!("{:<w$}", lexeme, w = MNEMONIC_WIDTH),
+                _ => format!("{:<w$}", lexeme, w = OPERAND_WIDTH),
+            })
+            .collect::<Vec<String>>()
+            .join(" ");
+
+        format!("{}{}", prefix, body).trim_end().to_string()
+    }
+}
+
+// mnemonics and registers are looked up before parsing, as `add` is also hex
+fn canonical(lexeme: &str) -> String {
+    if ASM_CONVERT.contains_right(lexeme) {
+        return lexeme.to_string();
+    }
+    match u16::from_str_radix(lexeme.trim_start_matches("0x"), 16) {
+        Ok(val) => format!("{:#06x}", val),
+        Err(_) => lexeme.to_string(),
+    }
+}
+
+/// Split assembly into lines, rejecting anything the assembler would
+pub fn parse(asm: &str, path: &Path) -> Result<Vec<Line>, SynacorErr> {
+    assemble_lines(asm, path)?;
+
+    let mut offset = 0;
+    let mut lines = Vec::new();
+
+    for text in asm.lines() {
+        let (code, comment) = match text.find(';') {
+            Some(pos) => (&text[..pos], Some(text[pos..].trim_end().to_string())),
+            None => (text, None),
+        };
+
+        let (addr, body) = match code.split_once(':') {
+            Some((head, tail)) => {
+                let head = head.trim();
+                match usize::from_str_radix(head.trim_start_matches("0x"), 16) {
+                    Ok(addr) => (Some(addr), tail),
+                    Err(_) => {
+                        let start = offset + (head.as_ptr() as usize - text.as_ptr() as usize);
+                        return Err(SynacorErr::new_code(
+                            start,
+                            start + head.len(),
+                            path.to_path_buf(),
+                            asm.to_string(),
+                            ErrorKind::Parse {
+                                lexeme: head.to_string(),
+                                expected: "a line address",
+                            },
+                        ));
+                    }
+                }
+            }
+            None => (None, code),
+        };
+
+        lines.push(Line {
+            addr,
+            lexemes: body.split_whitespace().map(canonical).collect(),
+            comment,
+        });
+        offset += text.len() + 1;
+    }
+
+    Ok(lines)
+}
+
+/// Lay out lines in the canonical format, ending with a newline
+pub fn render(lines: &[Line]) -> String {
+    let addresses = lines.iter().any(|line| line.addr.is_some());
+    let codes: Vec<String> = lines.iter().map(|line| line.code(addresses)).collect();
+    let comment_col = codes.iter().map(|code| code.len()).max().unwrap_or(0) + 1;
+
+    lines
+        .iter()
+        .zip(codes)
+        .map(|(line, code)| match &line.comment {
+            Some(comment) if code.is_empty() => comment.clone(),
+            Some(comment) => format!("{:<w$}{}", code, comment, w = comment_col),
+            None => code,
+        })
+        .map(|line| line + "\n")
+        .collect()
+}
+
+pub fn format(asm: &str, path: &Path) -> Result<String, SynacorErr> {
+    Ok(render(&parse(asm, path)?))
+}
+
+// Edit distance past which the lines left between the common start and end are reported as
+// one change, as when every line of a listing in another layout differs
+const MAX_EDITS: usize = 10_000;
+
+enum Edit {
+    Keep,
+    Remove(usize),
+    Add(usize),
+}
+
+type Snake = ((usize, usize), (usize, usize));
+
+/// The middle snake of Myers' diff, as where it starts and ends in both files, or `None` when
+/// more than `max_edits` lines are removed and added
+fn middle_snake(a: &[&str], b: &[&str], max_edits: usize) -> Option<Snake> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let delta = n - m;
+    let odd = delta % 2 != 0;
+    let max_d = ((n + m + 1) / 2).min(max_edits as isize / 2 + 1);
+    let at = |k: isize| (k + max_d + 1) as usize;
+
+    // lines equal going forward from the start, or backward from the end
+    let same = |x: isize, y: isize, reverse: bool| {
+        if reverse {
+            a[(n - 1 - x) as usize] == b[(m - 1 - y) as usize]
+        } else {
+            a[x as usize] == b[y as usize]
+        }
+    };
+    // where the furthest path on diagonal k = x - y after d edits starts and ends its snake
+    let snake = |v: &[isize], k: isize, d: isize, reverse: bool| {
+        let x = if k == -d || (k != d && v[at(k - 1)] < v[at(k + 1)]) {
+            v[at(k + 1)]
+        } else {
+            v[at(k - 1)] + 1
+        };
+        let mut end = x;
+        while end < n && end - k < m && same(end, end - k, reverse) {
+            end += 1;
+        }
+        (x, end)
+    };
+
+    // furthest x on each diagonal, with the backward one counted from the end of both files
+    let mut forward = vec![0; at(max_d + 1) + 1];
+    let mut backward = vec![0; at(max_d + 1) + 1];
+    for d in 0..=max_d {
+        for k in (-d..=d).step_by(2) {
+            let (x, end) = snake(&forward, k, d, false);
+            forward[at(k)] = end;
+            let back = delta - k;
+            if odd && (1 - d..d).contains(&back) && end + backward[at(back)] >= n {
+                return Some((
+                    (x as usize, (x - k) as usize),
+                    (end as usize, (end - k) as usize),
+                ));
+            }
+        }
+        for k in (-d..=d).step_by(2) {
+            let (x, end) = snake(&backward, k, d, true);
+            backward[at(k)] = end;
+            let ahead = delta - k;
+            if !odd && (-d..=d).contains(&ahead) && end + forward[at(ahead)] >= n {
+                let (x, end) = (n - end, n - x);
+                return Some((
+                    (x as usize, (x - ahead) as usize),
+                    (end as usize, (end - ahead) as usize),
+                ));
+            }
+        }
+    }
+    None
+}
+
+// Myers' linear space diff, matching the lines both files start and end with, then splitting
+// what is left on its middle snake
+fn diff_into(a: &[&str], b: &[&str], offset: (usize, usize), edits: &mut Vec<Edit>) {
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let (a, b) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+    let (x0, y0) = (offset.0 + prefix, offset.1 + prefix);
+
+    edits.extend((0..prefix).map(|_| Edit::Keep));
+    let snake = if a.is_empty() || b.is_empty() {
+        None
+    } else {
+        middle_snake(a, b, MAX_EDITS)
+    };
+    match snake {
+        Some(((x, y), (u, v))) => {
+            diff_into(&a[..x], &b[..y], (x0, y0), edits);
+            edits.extend((x..u).map(|_| Edit::Keep));
+            diff_into(&a[u..], &b[v..], (x0 + u, y0 + v), edits);
+        }
+        _ => {
+            edits.extend((0..a.len()).map(|x| Edit::Remove(x0 + x)));
+            edits.extend((0..b.len()).map(|y| Edit::Add(y0 + y)));
+        }
+    }
+    edits.extend((0..suffix).map(|_| Edit::Keep));
+}
+
+/// Runs of lines that differ between the original and formatted source, as the line number
+/// where each starts in the original, counted from one, with the lines removed and added
+pub fn changes<'a>(
+    original: &'a str,
+    formatted: &'a str,
+) -> Vec<(usize, Vec<&'a str>, Vec<&'a str>)> {
+    let a: Vec<&str> = original.lines().collect();
+    let b: Vec<&str> = formatted.lines().collect();
+
+    let mut hunks: Vec<(usize, Vec<&str>, Vec<&str>)> = Vec::new();
+    let mut line = 1;
+    let mut open = false;
+    let mut edits = Vec::new();
+    diff_into(&a, &b, (0, 0), &mut edits);
+    for edit in edits {
+        if matches!(edit, Edit::Keep) {
+            line += 1;
+            open = false;
+            continue;
+        }
+        if !open {
+            hunks.push((line, Vec::new(), Vec::new()));
+            open = true;
+        }
+        let (_, removed, added) = hunks.last_mut().expect("a hunk was just opened");
+        match edit {
+            Edit::Remove(x) => {
+                removed.push(a[x]);
+                line += 1;
+            }
+            Edit::Add(y) => added.push(b[y]),
+            Edit::Keep => (),
+        }
+    }
+    hunks
+}
@@ -1,8 +1,31 @@
 #[macro_use]
 extern crate lazy_static;
 
+pub mod admin;
+pub mod bf;
+pub mod cfg;
 pub mod cli;
+pub mod codes;
+pub mod compile;
 pub mod convert;
+pub mod decompile;
+pub mod diff;
+pub mod disasm;
 pub mod error;
+pub mod fmt;
+pub mod lint;
+pub mod lsp;
 pub mod opcodes;
+pub mod optimize;
+pub mod patch;
+pub mod prompt;
+pub mod script;
+pub mod session;
+pub mod slots;
+pub mod snapshot;
+pub mod solve;
+pub mod strings;
+pub mod vault;
 pub mod vm;
+pub mod world;
+pub mod xref;
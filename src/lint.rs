@@ -0,0 +1,296 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ops::Range;
+
+use clap::ValueEnum;
+use codespan_reporting::diagnostic::{Diagnostic, Label};
+use codespan_reporting::files::SimpleFiles;
+use codespan_reporting::term::emit;
+use codespan_reporting::term::termcolor::{ColorChoice, StandardStream};
+use serde_json::json;
+use strum_macros::Display;
+
+use crate::cfg::{Cfg, Function};
+use crate::convert::SourceMap;
+use crate::disasm::{Instruction, Operand};
+use crate::error::{span_json, MessageFormat};
+use crate::opcodes::OpName;
+
+#[derive(ValueEnum, Display, Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[strum(serialize_all = "kebab-case")]
+pub enum Lint {
+    /// Instructions that no path from the entry point reaches
+    UnreachableCode,
+    /// `wmem` to an address holding reachable code
+    SelfModification,
+    /// `pop` or `ret` that can run with nothing pushed by the function
+    StackUnderflow,
+    /// Jumps and calls landing inside another instruction
+    MidInstructionJump,
+    /// `out` of a literal outside 7-bit ASCII
+    NonAsciiOut,
+    /// `mod` by a literal zero
+    ModByZero,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Level {
+    Allow,
+    #[default]
+    Warn,
+    Deny,
+}
+
+/// Level of each lint, every one warns unless set otherwise
+#[derive(Debug, Clone, Default)]
+pub struct LintConfig {
+    levels: HashMap<Lint, Level>,
+}
+
+impl LintConfig {
+    pub fn level(&self, lint: Lint) -> Level {
+        self.levels.get(&lint).copied().unwrap_or_default()
+    }
+
+    pub fn set(&mut self, lint: Lint, level: Level) {
+        self.levels.insert(lint, level);
+    }
+}
+
+/// A lint finding, covering `len` values of memory starting at `addr`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    pub lint: Lint,
+    pub addr: usize,
+    pub len: usize,
+    pub message: String,
+}
+
+// instructions of the listing, in the same linear sweep as `u16_to_asm`
+fn sweep(memory: &[u16]) -> Vec<Instruction> {
+    let mut instructions = Vec::new();
+    let mut addr = 0;
+
+    while addr < memory.len() {
+        match Instruction::decode(memory, addr) {
+            Some(ins) => {
+                addr = ins.next_addr();
+                instructions.push(ins);
+            }
+            None => addr += 1,
+        }
+    }
+    instructions
+}
+
+fn reachable(cfg: &Cfg) -> BTreeMap<usize, Instruction> {
+    cfg.functions
+        .values()
+        .flat_map(|function| function.blocks.values())
+        .flat_map(|block| block.instructions.iter())
+        .map(|ins| (ins.addr, ins.clone()))
+        .collect()
+}
+
+fn unreachable_code(listing: &[Instruction], code: &BTreeMap<usize, Instruction>) -> Vec<Warning> {
+    let covered: BTreeSet<usize> = code
+        .values()
+        .flat_map(|ins| ins.addr..ins.next_addr())
+        .collect();
+
+    let mut runs: Vec<Range<usize>> = Vec::new();
+    for ins in listing.iter().filter(|ins| !covered.contains(&ins.addr)) {
+        match runs.last_mut() {
+            Some(run) if run.end == ins.addr => run.end = ins.next_addr(),
+            _ => runs.push(ins.addr..ins.next_addr()),
+        }
+    }
+
+    runs.into_iter()
+        .map(|run| Warning {
+            lint: Lint::UnreachableCode,
+            addr: run.start,
+            len: run.len(),
+            message: format!("Unreachable code at {:#06x}..{:#06x}.", run.start, run.end),
+        })
+        .collect()
+}
+
+fn stack_underflow(function: &Function, top_level: bool) -> Vec<Warning> {
+    // fewest values the function itself has pushed on any path into each block
+    let mut depths: BTreeMap<usize, usize> = BTreeMap::from([(function.entry, 0)]);
+    let mut todo = vec![function.entry];
+    let mut warnings: BTreeMap<usize, Warning> = BTreeMap::new();
+
+    while let Some(start) = todo.pop() {
+        let Some(block) = function.blocks.get(&start) else {
+            continue;
+        };
+        let mut depth = depths[&start];
+
+        for ins in block.instructions.iter() {
+            let message = match (ins.opname, depth, top_level) {
+                (OpName::Push, _, _) => {
+                    depth += 1;
+                    continue;
+                }
+                (OpName::Pop, 0, true) => "pop can run on an empty stack.",
+                (OpName::Pop, 0, false) => "pop can take the return address of the call.",
+                (OpName::Ret, 0, true) => "ret can run on an empty stack, halting the program.",
+                (OpName::Pop, _, _) => {
+                    depth -= 1;
+                    continue;
+                }
+                _ => continue,
+            };
+            warnings.entry(ins.addr).or_insert(Warning {
+                lint: Lint::StackUnderflow,
+                addr: ins.addr,
+                len: ins.size(),
+                message: message.to_string(),
+            });
+        }
+
+        for edge in block.successors.iter() {
+            match depths.get(&edge.target) {
+                Some(known) if *known <= depth => (),
+                _ => {
+                    depths.insert(edge.target, depth);
+                    todo.push(edge.target);
+                }
+            }
+        }
+    }
+
+    warnings.into_values().collect()
+}
+
+fn instruction_lints(
+    ins: &Instruction,
+    code: &BTreeMap<usize, Instruction>,
+    listing: &BTreeMap<usize, Instruction>,
+) -> Vec<(Lint, String)> {
+    let mut found = Vec::new();
+
+    match (ins.opname, ins.operands.as_slice()) {
+        (OpName::Wmem, [Operand::Literal(to), _]) => {
+            let to = *to as usize;
+            if let Some((_, target)) = code.range(..=to).next_back() {
+                if to < target.next_addr() {
+                    found.push((
+                        Lint::SelfModification,
+                        format!(
+                            "wmem overwrites the code at {:#06x}: {}.",
+                            target.addr, target
+                        ),
+                    ));
+                }
+            }
+        }
+        (OpName::Out, [Operand::Literal(val)]) if *val > 0x7f => found.push((
+            Lint::NonAsciiOut,
+            format!("out of {:#06x}, which is not an ASCII character.", val),
+        )),
+        (OpName::Mod, [_, _, Operand::Literal(0)]) => found.push((
+            Lint::ModByZero,
+            "mod by a literal zero always fails.".to_string(),
+        )),
+        _ => (),
+    }
+
+    if let Some(target) = ins.target() {
+        if let Some((_, covering)) = listing.range(..target).next_back() {
+            if target < covering.next_addr() {
+                found.push((
+                    Lint::MidInstructionJump,
+                    format!(
+                        "{} lands inside the instruction at {:#06x}: {}.",
+                        ins.opname, covering.addr, covering
+                    ),
+                ));
+            }
+        }
+    }
+
+    found
+}
+
+/// Lint findings for a program starting at address 0, leaving out allowed lints
+pub fn lint(memory: &[u16], config: &LintConfig) -> Vec<Warning> {
+    let cfg = Cfg::new(memory);
+    let code = reachable(&cfg);
+    let listing = sweep(memory);
+    let by_addr: BTreeMap<usize, Instruction> =
+        listing.iter().map(|ins| (ins.addr, ins.clone())).collect();
+
+    let mut warnings = unreachable_code(&listing, &code);
+
+    for function in cfg.functions.values() {
+        warnings.extend(stack_underflow(function, function.entry == 0));
+    }
+
+    for ins in code.values() {
+        for (lint, message) in instruction_lints(ins, &code, &by_addr) {
+            warnings.push(Warning {
+                lint,
+                addr: ins.addr,
+                len: ins.size(),
+                message,
+            });
+        }
+    }
+
+    warnings.retain(|warning| config.level(warning.lint) != Level::Allow);
+    warnings.sort_by_key(|warning| (warning.addr, warning.lint));
+    warnings
+}
+
+/// Report findings against the source they were assembled from, returning how many were
+/// denied
+pub fn emit_warnings(
+    warnings: &[Warning],
+    map: &SourceMap,
+    config: &LintConfig,
+    format: MessageFormat,
+    color: ColorChoice,
+) -> Result<usize, codespan_reporting::files::Error> {
+    let mut files = SimpleFiles::new();
+    let file_id = files.add(map.file.to_string_lossy(), &map.code);
+    let writer = StandardStream::stderr(color);
+    let term_config = codespan_reporting::term::Config::default();
+
+    let mut denied = 0;
+    for warning in warnings {
+        let deny = config.level(warning.lint) == Level::Deny;
+        denied += deny as usize;
+        let span = map.span(warning.addr, warning.len).unwrap_or(0..0);
+
+        if format == MessageFormat::Json {
+            eprintln!(
+                "{}",
+                json!({
+                    "severity": if deny { "error" } else { "warning" },
+                    "code": warning.lint.to_string(),
+                    "message": warning.message,
+                    "file": map.file.to_string_lossy(),
+                    "span": span_json(&map.code, span.start, span.end),
+                    "address": warning.addr,
+                    "notes": Vec::<String>::new(),
+                })
+            );
+        } else {
+            let diagnostic = if deny {
+                Diagnostic::error()
+            } else {
+                Diagnostic::warning()
+            };
+            let diagnostic = diagnostic
+                .with_code(warning.lint.to_string())
+                .with_message(&warning.message)
+                .with_labels(vec![Label::primary(file_id, span)]);
+
+            emit(&mut writer.lock(), &term_config, &files, &diagnostic)?;
+        }
+    }
+
+    Ok(denied)
+}
@@ -0,0 +1,500 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+    Notification as NotificationTrait, PublishDiagnostics,
+};
+use lsp_types::request::{
+    Completion, GotoDefinition, HoverRequest, Request as RequestTrait, SemanticTokensFullRequest,
+};
+use lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionOptions, CompletionResponse, Diagnostic,
+    DiagnosticSeverity, Documentation, GotoDefinitionResponse, Hover, HoverContents,
+    HoverProviderCapability, InitializeParams, Location as LspLocation, MarkupContent, MarkupKind,
+    OneOf, Position, PublishDiagnosticsParams, Range, SemanticToken, SemanticTokenType,
+    SemanticTokens, SemanticTokensFullOptions, SemanticTokensLegend, SemanticTokensOptions,
+    SemanticTokensResult, SemanticTokensServerCapabilities, ServerCapabilities,
+    TextDocumentSyncCapability, TextDocumentSyncKind, Url,
+};
+
+use crate::convert::{assemble_lines, assemble_with_map};
+use crate::error::{ErrorKind, Location, SynacorErr};
+use crate::opcodes::{OpName, ASM_CONVERT, INS_WIDTH};
+
+// Editor support for assembly files over stdio. The requests themselves are answered by the
+// plain functions below, which only need the document text.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TokenKind {
+    /// Optional `0x0000:` line address
+    Label(u16),
+    Mnemonic(OpName),
+    Register(u16),
+    Number(u16),
+    Data,
+    Comment,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Token {
+    line: usize,
+    /// Byte columns within the line
+    start: usize,
+    end: usize,
+    kind: TokenKind,
+}
+
+fn parse_hex(lexeme: &str) -> Option<u16> {
+    u16::from_str_radix(lexeme.trim_start_matches("0x"), 16).ok()
+}
+
+// same split as the assembler, but keeping addresses and comments; unparsable lexemes are
+// left out since the assembler already reports them
+fn tokenize(line_no: usize, line: &str) -> Vec<Token> {
+    let offset = |s: &str| s.as_ptr() as usize - line.as_ptr() as usize;
+    let token = |s: &str, kind| Token {
+        line: line_no,
+        start: offset(s),
+        end: offset(s) + s.len(),
+        kind,
+    };
+
+    let (code, comment) = match line.find(';') {
+        Some(pos) => (&line[..pos], Some(&line[pos..])),
+        None => (line, None),
+    };
+
+    let mut tokens = Vec::new();
+
+    let body = match code.split_once(':') {
+        Some((head, tail)) => {
+            let label = head.trim();
+            if let Some(addr) = parse_hex(label) {
+                tokens.push(token(label, TokenKind::Label(addr)));
+            }
+            tail
+        }
+        None => code,
+    };
+
+    for lexeme in body.split_whitespace() {
+        let kind = match ASM_CONVERT.get_by_right(lexeme) {
+            Some(val) if *val >= 32768 => Some(TokenKind::Register(*val)),
+            Some(val) => OpName::try_from(*val).ok().map(TokenKind::Mnemonic),
+            None if lexeme == "data" => Some(TokenKind::Data),
+            None => parse_hex(lexeme).map(TokenKind::Number),
+        };
+        if let Some(kind) = kind {
+            tokens.push(token(lexeme, kind));
+        }
+    }
+
+    if let Some(comment) = comment {
+        tokens.push(token(comment, TokenKind::Comment));
+    }
+
+    tokens
+}
+
+fn tokens(text: &str) -> Vec<Token> {
+    text.lines()
+        .enumerate()
+        .flat_map(|(line_no, line)| tokenize(line_no, line))
+        .collect()
+}
+
+// LSP columns count UTF-16 code units
+fn utf16_col(line: &str, byte: usize) -> u32 {
+    line[..byte.min(line.len())].encode_utf16().count() as u32
+}
+
+fn byte_col(line: &str, col: u32) -> usize {
+    let mut units = 0;
+    for (byte, c) in line.char_indices() {
+        if units >= col as usize {
+            return byte;
+        }
+        units += c.len_utf16();
+    }
+    line.len()
+}
+
+fn token_range(text: &str, token: &Token) -> Range {
+    let line = text.lines().nth(token.line).unwrap_or_default();
+    Range::new(
+        Position::new(token.line as u32, utf16_col(line, token.start)),
+        Position::new(token.line as u32, utf16_col(line, token.end)),
+    )
+}
+
+fn position(text: &str, offset: usize) -> Position {
+    let before = &text[..offset.min(text.len())];
+    let line_start = before.rfind('\n').map_or(0, |pos| pos + 1);
+    Position::new(
+        before.matches('\n').count() as u32,
+        before[line_start..].encode_utf16().count() as u32,
+    )
+}
+
+fn token_at(text: &str, pos: Position) -> Option<Token> {
+    let line = text.lines().nth(pos.line as usize)?;
+    let col = byte_col(line, pos.character);
+    tokenize(pos.line as usize, line)
+        .into_iter()
+        .find(|token| token.start <= col && col <= token.end)
+}
+
+/// Assembler errors for a document, at most one since assembly stops at the first
+pub fn diagnostics(text: &str, path: &Path) -> Vec<Diagnostic> {
+    match assemble_lines(text, path) {
+        Ok(_) => Vec::new(),
+        Err(SynacorErr {
+            location: Location::Code { start, end, .. },
+            kind,
+            ..
+        }) => vec![Diagnostic {
+            range: Range::new(position(text, start), position(text, end)),
+            severity: Some(DiagnosticSeverity::ERROR),
+            source: Some("synacor".to_string()),
+            message: kind.to_string(),
+            ..Default::default()
+        }],
+        Err(_) => Vec::new(),
+    }
+}
+
+fn markdown(value: String) -> HoverContents {
+    HoverContents::Markup(MarkupContent {
+        kind: MarkupKind::Markdown,
+        value,
+    })
+}
+
+/// Opcode description and width for mnemonics, values for registers and literals
+pub fn hover(text: &str, pos: Position) -> Option<Hover> {
+    let token = token_at(text, pos)?;
+
+    let value = match token.kind {
+        TokenKind::Mnemonic(opname) => {
+            let opcode = opname as u16;
+            format!(
+                "```\n{}\n```\n{}\n\nopcode {}, {} values wide",
+                opname.signature(),
+                opname.description(),
+                opcode,
+                INS_WIDTH[&opcode] + 1
+            )
+        }
+        TokenKind::Register(val) => format!("register `${}`, encoded as {}", val - 32768, val),
+        TokenKind::Number(val) => format!("`{:#06x}` = {}", val, val),
+        TokenKind::Label(addr) => format!("address `{:#06x}` = {}", addr, addr),
+        TokenKind::Data => "raw values, assembled as they are".to_string(),
+        TokenKind::Comment => return None,
+    };
+
+    Some(Hover {
+        contents: markdown(value),
+        range: Some(token_range(text, &token)),
+    })
+}
+
+/// Where the address under the cursor is defined: the line labelled with it, or failing
+/// that the instruction the assembler places there
+pub fn definition(text: &str, pos: Position) -> Option<Range> {
+    let TokenKind::Number(addr) = token_at(text, pos)?.kind else {
+        return None;
+    };
+
+    let all = tokens(text);
+    if let Some(label) = all
+        .iter()
+        .find(|token| token.kind == TokenKind::Label(addr))
+    {
+        return Some(token_range(text, label));
+    }
+
+    let (_, map) = assemble_with_map(text.to_string(), Path::new("")).ok()?;
+    let span = map.spans.get(addr as usize)?;
+    Some(Range::new(
+        position(text, span.start),
+        position(text, span.end),
+    ))
+}
+
+/// Mnemonics at the start of an instruction, registers after it
+pub fn completions(text: &str, pos: Position) -> Vec<CompletionItem> {
+    let line = text.lines().nth(pos.line as usize).unwrap_or_default();
+    let col = byte_col(line, pos.character);
+
+    if line[..col].contains(';') {
+        return Vec::new();
+    }
+
+    // anything other than the line address before the word being typed
+    let operands = tokenize(pos.line as usize, line)
+        .iter()
+        .any(|token| token.end < col && !matches!(token.kind, TokenKind::Label(_)));
+
+    if operands {
+        (0..8)
+            .map(|reg| CompletionItem {
+                label: format!("${}", reg),
+                kind: Some(CompletionItemKind::VARIABLE),
+                detail: Some(format!("register {}", reg)),
+                ..Default::default()
+            })
+            .collect()
+    } else {
+        (0..22)
+            .filter_map(|opcode| OpName::try_from(opcode).ok())
+            .map(|opname| CompletionItem {
+                label: opname.to_string(),
+                kind: Some(CompletionItemKind::KEYWORD),
+                detail: Some(opname.signature()),
+                documentation: Some(Documentation::String(opname.description().to_string())),
+                ..Default::default()
+            })
+            .chain([CompletionItem {
+                label: "data".to_string(),
+                kind: Some(CompletionItemKind::KEYWORD),
+                detail: Some("raw values".to_string()),
+                ..Default::default()
+            }])
+            .collect()
+    }
+}
+
+const TOKEN_TYPES: [SemanticTokenType; 5] = [
+    SemanticTokenType::new("label"),
+    SemanticTokenType::KEYWORD,
+    SemanticTokenType::VARIABLE,
+    SemanticTokenType::NUMBER,
+    SemanticTokenType::COMMENT,
+];
+
+/// Semantic tokens, delta encoded as the protocol expects
+pub fn semantic_tokens(text: &str) -> Vec<SemanticToken> {
+    let lines: Vec<&str> = text.lines().collect();
+    let (mut prev_line, mut prev_start) = (0, 0);
+
+    tokens(text)
+        .iter()
+        .map(|token| {
+            let line = lines[token.line];
+            let start = utf16_col(line, token.start);
+            let token_type = match token.kind {
+                TokenKind::Label(_) => 0,
+                TokenKind::Mnemonic(_) | TokenKind::Data => 1,
+                TokenKind::Register(_) => 2,
+                TokenKind::Number(_) => 3,
+                TokenKind::Comment => 4,
+            };
+
+            let line_no = token.line as u32;
+            let delta_start = if line_no == prev_line {
+                start - prev_start
+            } else {
+                start
+            };
+            let encoded = SemanticToken {
+                delta_line: line_no - prev_line,
+                delta_start,
+                length: utf16_col(line, token.end) - start,
+                token_type,
+                token_modifiers_bitset: 0,
+            };
+            (prev_line, prev_start) = (line_no, start);
+            encoded
+        })
+        .collect()
+}
+
+fn capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        completion_provider: Some(CompletionOptions::default()),
+        semantic_tokens_provider: Some(SemanticTokensServerCapabilities::SemanticTokensOptions(
+            SemanticTokensOptions {
+                legend: SemanticTokensLegend {
+                    token_types: TOKEN_TYPES.to_vec(),
+                    token_modifiers: Vec::new(),
+                },
+                full: Some(SemanticTokensFullOptions::Bool(true)),
+                ..Default::default()
+            },
+        )),
+        ..Default::default()
+    }
+}
+
+fn protocol_err(e: impl ToString) -> SynacorErr {
+    SynacorErr::new_io(ErrorKind::Io(e.to_string()))
+}
+
+fn uri_path(uri: &Url) -> PathBuf {
+    uri.to_file_path()
+        .unwrap_or_else(|_| PathBuf::from(uri.as_str()))
+}
+
+struct Server {
+    connection: Connection,
+    documents: HashMap<Url, String>,
+}
+
+impl Server {
+    fn send(&self, message: Message) -> Result<(), SynacorErr> {
+        self.connection.sender.send(message).map_err(protocol_err)
+    }
+
+    fn publish(&self, uri: Url) -> Result<(), SynacorErr> {
+        let diagnostics = match self.documents.get(&uri) {
+            Some(text) => diagnostics(text, &uri_path(&uri)),
+            None => Vec::new(),
+        };
+        let params = PublishDiagnosticsParams::new(uri, diagnostics, None);
+        self.send(Message::Notification(Notification::new(
+            PublishDiagnostics::METHOD.to_string(),
+            params,
+        )))
+    }
+
+    fn notification(&mut self, not: Notification) -> Result<(), SynacorErr> {
+        match not.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params: <DidOpenTextDocument as NotificationTrait>::Params =
+                    serde_json::from_value(not.params).map_err(protocol_err)?;
+                let uri = params.text_document.uri;
+                self.documents
+                    .insert(uri.clone(), params.text_document.text);
+                self.publish(uri)
+            }
+            DidChangeTextDocument::METHOD => {
+                let params: <DidChangeTextDocument as NotificationTrait>::Params =
+                    serde_json::from_value(not.params).map_err(protocol_err)?;
+                let uri = params.text_document.uri;
+                // full sync, so the last change holds the whole document
+                if let Some(change) = params.content_changes.into_iter().last() {
+                    self.documents.insert(uri.clone(), change.text);
+                }
+                self.publish(uri)
+            }
+            DidCloseTextDocument::METHOD => {
+                let params: <DidCloseTextDocument as NotificationTrait>::Params =
+                    serde_json::from_value(not.params).map_err(protocol_err)?;
+                self.documents.remove(&params.text_document.uri);
+                self.publish(params.text_document.uri)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn text(&self, uri: &Url) -> &str {
+        self.documents.get(uri).map_or("", |text| text.as_str())
+    }
+
+    fn request(&self, req: Request) -> Result<(), SynacorErr> {
+        let id = req.id.clone();
+        let parse_err = |e: serde_json::Error| {
+            Response::new_err(id.clone(), ErrorCode::InvalidParams as i32, e.to_string())
+        };
+
+        let response = match req.method.as_str() {
+            HoverRequest::METHOD => {
+                match serde_json::from_value::<<HoverRequest as RequestTrait>::Params>(req.params) {
+                    Ok(params) => {
+                        let at = params.text_document_position_params;
+                        let result = hover(self.text(&at.text_document.uri), at.position);
+                        Response::new_ok(id, result)
+                    }
+                    Err(e) => parse_err(e),
+                }
+            }
+            GotoDefinition::METHOD => {
+                match serde_json::from_value::<<GotoDefinition as RequestTrait>::Params>(req.params)
+                {
+                    Ok(params) => {
+                        let at = params.text_document_position_params;
+                        let uri = at.text_document.uri;
+                        let result = definition(self.text(&uri), at.position).map(|range| {
+                            GotoDefinitionResponse::Scalar(LspLocation::new(uri.clone(), range))
+                        });
+                        Response::new_ok(id, result)
+                    }
+                    Err(e) => parse_err(e),
+                }
+            }
+            Completion::METHOD => {
+                match serde_json::from_value::<<Completion as RequestTrait>::Params>(req.params) {
+                    Ok(params) => {
+                        let at = params.text_document_position;
+                        let items = completions(self.text(&at.text_document.uri), at.position);
+                        Response::new_ok(id, CompletionResponse::Array(items))
+                    }
+                    Err(e) => parse_err(e),
+                }
+            }
+            SemanticTokensFullRequest::METHOD => {
+                match serde_json::from_value::<<SemanticTokensFullRequest as RequestTrait>::Params>(
+                    req.params,
+                ) {
+                    Ok(params) => {
+                        let data = semantic_tokens(self.text(&params.text_document.uri));
+                        Response::new_ok(
+                            id,
+                            SemanticTokensResult::Tokens(SemanticTokens {
+                                result_id: None,
+                                data,
+                            }),
+                        )
+                    }
+                    Err(e) => parse_err(e),
+                }
+            }
+            method => Response::new_err(
+                id,
+                ErrorCode::MethodNotFound as i32,
+                format!("Unsupported request {}", method),
+            ),
+        };
+
+        self.send(Message::Response(response))
+    }
+}
+
+/// Run the language server on stdin and stdout until the client shuts it down
+pub fn serve() -> Result<(), SynacorErr> {
+    let (connection, io_threads) = Connection::stdio();
+
+    let capabilities = serde_json::to_value(capabilities()).map_err(protocol_err)?;
+    let init = connection.initialize(capabilities).map_err(protocol_err)?;
+    let _: InitializeParams = serde_json::from_value(init).map_err(protocol_err)?;
+
+    let mut server = Server {
+        connection,
+        documents: HashMap::new(),
+    };
+
+    while let Ok(message) = server.connection.receiver.recv() {
+        match message {
+            Message::Request(req) => {
+                if server
+                    .connection
+                    .handle_shutdown(&req)
+                    .map_err(protocol_err)?
+                {
+                    break;
+                }
+                server.request(req)?;
+            }
+            Message::Notification(not) => server.notification(not)?,
+            Message::Response(_) => (),
+        }
+    }
+
+    drop(server);
+    io_threads.join().map_err(protocol_err)
+}
@@ -1,7 +1,11 @@
 use bimap::BiHashMap;
 use std::collections::HashMap;
+use strum_macros::Display;
 
-#[derive(Debug)]
+const BITS_15: usize = 32768;
+
+#[derive(Display, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[strum(serialize_all = "lowercase")]
 pub enum OpName {
     Halt,
     Set,
@@ -29,9 +33,84 @@ pub enum OpName {
 
 impl OpName {
     pub fn advance(&self) -> bool {
+        !matches!(
+            self,
+            Self::Halt | Self::Jmp | Self::Jt | Self::Jf | Self::Ret | Self::Call
+        )
+    }
+
+    // instructions after which execution never falls through to the next address
+    pub fn terminates(&self) -> bool {
+        matches!(self, Self::Halt | Self::Jmp | Self::Ret)
+    }
+
+    // result of the arithmetic and comparison opcodes, None for anything else or a zero modulus
+    pub fn eval(&self, b: u16, c: u16) -> Option<u16> {
+        let res = match self {
+            Self::Eq => (b == c) as u16,
+            Self::Gt => (b > c) as u16,
+            Self::Add => ((b as usize + c as usize) % BITS_15) as u16,
+            Self::Mult => ((b as usize) * (c as usize) % BITS_15) as u16,
+            Self::Mod => b.checked_rem(c)?,
+            Self::And => b & c,
+            Self::Or => b | c,
+            Self::Not => !b % (BITS_15 as u16),
+            _ => return None,
+        };
+        Some(res)
+    }
+
+    /// What the instruction does, as worded in the architecture spec
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::Halt => "stop execution and terminate the program",
+            Self::Set => "set register <a> to the value of <b>",
+            Self::Push => "push <a> onto the stack",
+            Self::Pop => {
+                "remove the top element from the stack and write it into <a>; empty stack = error"
+            }
+            Self::Eq => "set <a> to 1 if <b> is equal to <c>; set it to 0 otherwise",
+            Self::Gt => "set <a> to 1 if <b> is greater than <c>; set it to 0 otherwise",
+            Self::Jmp => "jump to <a>",
+            Self::Jt => "if <a> is nonzero, jump to <b>",
+            Self::Jf => "if <a> is zero, jump to <b>",
+            Self::Add => "assign into <a> the sum of <b> and <c> (modulo 32768)",
+            Self::Mult => "store into <a> the product of <b> and <c> (modulo 32768)",
+            Self::Mod => "store into <a> the remainder of <b> divided by <c>",
+            Self::And => "stores into <a> the bitwise and of <b> and <c>",
+            Self::Or => "stores into <a> the bitwise or of <b> and <c>",
+            Self::Not => "stores 15-bit bitwise inverse of <b> in <a>",
+            Self::Rmem => "read memory at address <b> and write it to <a>",
+            Self::Wmem => "write the value from <b> into memory at address <a>",
+            Self::Call => "write the address of the next instruction to the stack and jump to <a>",
+            Self::Ret => "remove the top element from the stack and jump to it; empty stack = halt",
+            Self::Out => "write the character represented by ascii code <a> to the terminal",
+            Self::In => "read a character from the terminal and write its ascii code to <a>",
+            Self::Noop => "no operation",
+        }
+    }
+
+    // mnemonic followed by its operand names, e.g. `add a b c`
+    pub fn signature(&self) -> String {
+        let width = INS_WIDTH[&(*self as u16)];
+        [self.to_string()]
+            .into_iter()
+            .chain(["a", "b", "c"].iter().take(width).map(|s| s.to_string()))
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+
+    // infix operator for the binary opcodes handled by `eval`
+    pub fn operator(&self) -> Option<&'static str> {
         match self {
-            Self::Halt | Self::Jmp | Self::Jt | Self::Jf | Self::Ret | Self::Call => false,
-            _ => true,
+            Self::Eq => Some("=="),
+            Self::Gt => Some(">"),
+            Self::Add => Some("+"),
+            Self::Mult => Some("*"),
+            Self::Mod => Some("%"),
+            Self::And => Some("&"),
+            Self::Or => Some("|"),
+            _ => None,
         }
     }
 }
@@ -0,0 +1,418 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::cfg::Cfg;
+use crate::disasm::{Instruction, Operand};
+use crate::error::{ErrorKind, SynacorErr};
+use crate::opcodes::OpName;
+
+// Peephole passes over the reachable code of a program. Every value keeps the address it
+// was loaded at as its label, and literal jump, call, rmem and wmem operands refer to those
+// labels until the program is laid out again. A label whose value was removed moves on to
+// the next value that is kept. Code after a jmp, ret or halt that nothing jumps to is
+// dropped as well.
+//
+// Addresses that are computed or pushed as plain values cannot be relocated, and code that
+// is read or written changes as it is rewritten. Programs are refused for jumps, calls, rmem
+// or wmem through a register, rmem or wmem of code, and a literal pushed then returned to by
+// a ret in the same block.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Item {
+    Ins(Instruction),
+    Data(u16),
+}
+
+#[derive(Debug, Clone)]
+struct Slot {
+    /// Address in the original program
+    label: usize,
+    item: Item,
+}
+
+fn err(addr: usize, details: String) -> SynacorErr {
+    SynacorErr::new_addr(addr, ErrorKind::Optimize(details))
+}
+
+// literal operands that hold an address, by index
+fn reference_operands(ins: &Instruction) -> Vec<usize> {
+    match ins.opname {
+        OpName::Jmp | OpName::Call | OpName::Wmem => vec![0],
+        OpName::Jt | OpName::Jf | OpName::Rmem => vec![1],
+        _ => Vec::new(),
+    }
+}
+
+// operands holding values that are read, and so can be replaced by known constants
+fn value_operands(ins: &Instruction) -> Vec<usize> {
+    match ins.opname {
+        OpName::Push | OpName::Out | OpName::Jt | OpName::Jf => vec![0],
+        OpName::Set | OpName::Not | OpName::Wmem => vec![1],
+        OpName::Eq
+        | OpName::Gt
+        | OpName::Add
+        | OpName::Mult
+        | OpName::Mod
+        | OpName::And
+        | OpName::Or => vec![1, 2],
+        _ => Vec::new(),
+    }
+}
+
+fn slots(memory: &[u16]) -> Result<Vec<Slot>, SynacorErr> {
+    let cfg = Cfg::new(memory);
+    let blocks = || {
+        cfg.functions
+            .values()
+            .flat_map(|function| function.blocks.values())
+    };
+    let code: BTreeMap<usize, Instruction> = blocks()
+        .flat_map(|block| block.instructions.iter())
+        .map(|ins| (ins.addr, ins.clone()))
+        .collect();
+
+    for block in blocks() {
+        let mut pushed: Vec<Operand> = Vec::new();
+        for ins in block.instructions.iter() {
+            match ins.opname {
+                OpName::Push => pushed.push(ins.operands[0]),
+                OpName::Pop => {
+                    pushed.pop();
+                }
+                OpName::Ret => {
+                    if let Some(Operand::Literal(to)) = pushed.pop() {
+                        return Err(err(
+                            ins.addr,
+                            format!("ret returns to {:#06x}, pushed as a plain value.", to),
+                        ));
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    let mut slots = Vec::new();
+    let mut addr = 0;
+    while addr < memory.len() {
+        match code.get(&addr) {
+            Some(ins) => {
+                if let Some((inner, _)) = code.range(addr + 1..ins.next_addr()).next() {
+                    return Err(err(
+                        *inner,
+                        format!(
+                            "Code at {:#06x} overlaps the instruction at {:#06x}.",
+                            inner, addr
+                        ),
+                    ));
+                }
+                slots.push(Slot {
+                    label: addr,
+                    item: Item::Ins(ins.clone()),
+                });
+                addr = ins.next_addr();
+            }
+            None => {
+                slots.push(Slot {
+                    label: addr,
+                    item: Item::Data(memory[addr]),
+                });
+                addr += 1;
+            }
+        }
+    }
+
+    let starts: BTreeSet<usize> = slots.iter().map(|slot| slot.label).collect();
+    for slot in slots.iter() {
+        let Item::Ins(ins) = &slot.item else {
+            continue;
+        };
+        for i in reference_operands(ins) {
+            let to = match ins.operands[i] {
+                Operand::Literal(to) => to,
+                Operand::Register(reg) => {
+                    return Err(err(
+                        ins.addr,
+                        format!(
+                            "{} through ${} has an address that cannot be relocated.",
+                            ins.opname, reg
+                        ),
+                    ))
+                }
+                _ => continue,
+            };
+            let to = to as usize;
+            if !starts.contains(&to) && to != memory.len() {
+                return Err(err(
+                    ins.addr,
+                    format!(
+                        "{} refers to {:#06x}, inside an instruction.",
+                        ins.opname, to
+                    ),
+                ));
+            }
+            if ins.opname == OpName::Wmem && code.contains_key(&to) {
+                return Err(err(
+                    ins.addr,
+                    format!("wmem modifies the code at {:#06x}.", to),
+                ));
+            }
+            if ins.opname == OpName::Rmem && code.contains_key(&to) {
+                return Err(err(
+                    ins.addr,
+                    format!("rmem reads the code at {:#06x}.", to),
+                ));
+            }
+        }
+    }
+
+    Ok(slots)
+}
+
+fn set(addr: usize, reg: Operand, val: Operand) -> Instruction {
+    Instruction {
+        addr,
+        opname: OpName::Set,
+        operands: vec![reg, val],
+    }
+}
+
+// constants and dead instructions within straight-line code, where `labels` are the
+// addresses that can be jumped to. A slot is jumped to when any label between it and the
+// slot before it points there, since removed slots pass their labels on.
+fn fold(slots: Vec<Slot>, labels: &BTreeSet<usize>) -> (Vec<Slot>, bool) {
+    let mut out: Vec<Slot> = Vec::with_capacity(slots.len());
+    let mut known: [Option<u16>; 8] = [None; 8];
+    let mut changed = false;
+    let mut it = slots.into_iter().peekable();
+
+    let mut prev_label = None;
+    // after a jmp, ret or halt, until something jumps back in
+    let mut dead = false;
+    let jumped_to = |after: Option<usize>, label: usize| {
+        let from = after.map_or(0, |after| after + 1);
+        labels.range(from..=label).next().is_some()
+    };
+
+    while let Some(slot) = it.next() {
+        if jumped_to(prev_label, slot.label) {
+            known = [None; 8];
+            dead = false;
+        }
+        prev_label = Some(slot.label);
+        let Item::Ins(mut ins) = slot.item else {
+            known = [None; 8];
+            dead = false;
+            out.push(slot);
+            continue;
+        };
+        if dead {
+            changed = true;
+            continue;
+        }
+
+        for i in value_operands(&ins) {
+            if let Operand::Register(reg) = ins.operands[i] {
+                if let Some(val) = known[reg as usize] {
+                    ins.operands[i] = Operand::Literal(val);
+                    changed = true;
+                }
+            }
+        }
+
+        let next_label = it.peek().map(|next| next.label);
+        // register written by a `pop` that directly follows and is not jumped to
+        let next_pop = match it.peek() {
+            Some(Slot {
+                label,
+                item: Item::Ins(pop),
+            }) if pop.opname == OpName::Pop && !jumped_to(Some(slot.label), *label) => {
+                Some(pop.operands[0])
+            }
+            _ => None,
+        };
+        let ops = ins.operands.clone();
+
+        let folded = match (ins.opname, ops.as_slice()) {
+            (OpName::Noop, _) => None,
+            (OpName::Set, [a, b]) if a == b => None,
+            (OpName::Jt, [Operand::Literal(val), target])
+            | (OpName::Jf, [Operand::Literal(val), target]) => {
+                let taken = (*val != 0) == (ins.opname == OpName::Jt);
+                taken.then(|| Instruction {
+                    addr: ins.addr,
+                    opname: OpName::Jmp,
+                    operands: vec![*target],
+                })
+            }
+            (OpName::Not, [a, Operand::Literal(b)])
+            | (_, [a, Operand::Literal(b), Operand::Literal(_)]) => {
+                let c = ops.get(2).and_then(|c| c.literal()).unwrap_or(0);
+                match ins.opname.eval(*b, c) {
+                    Some(val) => Some(set(ins.addr, *a, Operand::Literal(val))),
+                    None => Some(ins.clone()),
+                }
+            }
+            (OpName::Jmp, [Operand::Literal(to)])
+                if next_label
+                    .is_some_and(|next| (slot.label + 1..=next).contains(&(*to as usize))) =>
+            {
+                None
+            }
+            (OpName::Push, [val]) => match next_pop {
+                Some(reg) => {
+                    prev_label = it.next().map(|pop| pop.label);
+                    changed = true;
+                    (*val != reg).then(|| set(ins.addr, reg, *val))
+                }
+                None => Some(ins.clone()),
+            },
+            _ => Some(ins.clone()),
+        };
+
+        match folded {
+            Some(new) => {
+                if new != ins {
+                    changed = true;
+                }
+
+                // what is known about the registers after this instruction runs
+                match (new.opname, new.operands.as_slice()) {
+                    (OpName::Set, [Operand::Register(reg), Operand::Literal(val)]) => {
+                        known[*reg as usize] = Some(*val)
+                    }
+                    (OpName::Call, _) => known = [None; 8],
+                    _ => {
+                        if let Some(reg) = new.dest() {
+                            known[reg as usize] = None;
+                        }
+                    }
+                }
+                if new.opname.terminates() {
+                    known = [None; 8];
+                    dead = true;
+                }
+
+                out.push(Slot {
+                    label: slot.label,
+                    item: Item::Ins(new),
+                });
+            }
+            None => changed = true,
+        }
+    }
+
+    (out, changed)
+}
+
+// first kept slot at or after each label
+fn resolve(slots: &[Slot]) -> BTreeMap<usize, usize> {
+    slots
+        .iter()
+        .enumerate()
+        .map(|(i, slot)| (slot.label, i))
+        .collect()
+}
+
+fn target_slot(index: &BTreeMap<usize, usize>, label: usize) -> Option<usize> {
+    index.range(label..).next().map(|(_, i)| *i)
+}
+
+// jumps and calls to a `jmp` go straight to where it leads
+fn thread_jumps(slots: &mut [Slot]) -> bool {
+    let index = resolve(slots);
+    let mut changed = false;
+
+    for i in 0..slots.len() {
+        let Item::Ins(ins) = &slots[i].item else {
+            continue;
+        };
+        let Some(mut to) = ins.target() else {
+            continue;
+        };
+        let operand = if matches!(ins.opname, OpName::Jt | OpName::Jf) {
+            1
+        } else {
+            0
+        };
+
+        let mut seen = BTreeSet::new();
+        while let Some(Item::Ins(next)) = target_slot(&index, to).map(|j| &slots[j].item) {
+            match next.target() {
+                Some(next_to) if next.opname == OpName::Jmp && seen.insert(next_to) => to = next_to,
+                _ => break,
+            }
+        }
+
+        if let Item::Ins(ins) = &mut slots[i].item {
+            if ins.operands[operand] != Operand::Literal(to as u16) {
+                ins.operands[operand] = Operand::Literal(to as u16);
+                changed = true;
+            }
+        }
+    }
+
+    changed
+}
+
+fn labels(slots: &[Slot]) -> BTreeSet<usize> {
+    slots
+        .iter()
+        .filter_map(|slot| match &slot.item {
+            Item::Ins(ins) => ins.target(),
+            Item::Data(_) => None,
+        })
+        .collect()
+}
+
+// lay the slots out again, moving every reference to the new address of its label
+fn layout(slots: &[Slot]) -> Vec<u16> {
+    let mut new_addr = BTreeMap::new();
+    let mut addr = 0;
+    for slot in slots {
+        new_addr.insert(slot.label, addr);
+        addr += match &slot.item {
+            Item::Ins(ins) => ins.size(),
+            Item::Data(_) => 1,
+        };
+    }
+    let relocate = |label: u16| -> u16 {
+        new_addr
+            .range(label as usize..)
+            .next()
+            .map_or(addr, |(_, new)| *new) as u16
+    };
+
+    slots
+        .iter()
+        .flat_map(|slot| match &slot.item {
+            Item::Ins(ins) => {
+                let mut ins = ins.clone();
+                for i in reference_operands(&ins) {
+                    if let Operand::Literal(label) = ins.operands[i] {
+                        ins.operands[i] = Operand::Literal(relocate(label));
+                    }
+                }
+                ins.encode()
+            }
+            Item::Data(val) => vec![*val],
+        })
+        .collect()
+}
+
+/// Smaller program with the same behaviour, for programs whose code addresses are all
+/// literal jump and call targets
+pub fn optimize(memory: &[u16]) -> Result<Vec<u16>, SynacorErr> {
+    let mut slots = slots(memory)?;
+
+    loop {
+        let labels = labels(&slots);
+        let (folded, folded_changed) = fold(slots, &labels);
+        slots = folded;
+        let threaded = thread_jumps(&mut slots);
+        if !folded_changed && !threaded {
+            break;
+        }
+    }
+
+    Ok(layout(&slots))
+}
@@ -0,0 +1,152 @@
+use std::path::{Path, PathBuf};
+
+use crate::cli::parse_addr;
+use crate::convert::assemble_lines;
+use crate::disasm::Instruction;
+use crate::error::{ErrorKind, SynacorErr};
+
+/// Values to write over memory starting at `addr`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk {
+    pub addr: usize,
+    pub words: Vec<u16>,
+}
+
+impl Hunk {
+    /// A single hunk from an assembly snippet given on the command line
+    pub fn from_asm(addr: usize, asm: &str) -> Result<Self, SynacorErr> {
+        let words = assemble_lines(asm, Path::new("<asm>"))?.concat();
+        Ok(Self { addr, words })
+    }
+}
+
+// Patch files are assembly split into hunks by `@<addr>` lines. Addresses are read like
+// `--at`, as hex with a 0x prefix and decimal without one, while the assembly keeps the
+// assembler's hex literals:
+//
+// ; skip the confirmation
+// @0x1571
+// noop noop
+// @5511
+// set $7 0x0001
+
+pub fn parse_patch_file(path: &PathBuf) -> Result<Vec<Hunk>, SynacorErr> {
+    let text = std::fs::read_to_string(path)?;
+
+    // headers become comments so the assembler keeps the same line and column numbers
+    let asm: String = text
+        .lines()
+        .map(|line| match line.trim_start().strip_prefix('@') {
+            Some(_) => line.replacen('@', ";", 1),
+            None => line.to_string(),
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+    let lines = assemble_lines(&asm, path)?;
+
+    let mut hunks: Vec<Hunk> = Vec::new();
+    let mut offset = 0;
+
+    for (line, words) in text.lines().zip(lines) {
+        if let Some(header) = line.trim_start().strip_prefix('@') {
+            let addr_str = header.split(';').next().unwrap_or_default().trim();
+            let start = offset + line.find('@').unwrap_or_default();
+
+            match parse_addr(addr_str) {
+                Ok(addr) => hunks.push(Hunk {
+                    addr,
+                    words: Vec::new(),
+                }),
+                Err(_) => {
+                    return Err(SynacorErr::new_code(
+                        start,
+                        start + header.len() + 1,
+                        path.to_path_buf(),
+                        text.clone(),
+                        ErrorKind::Patch(format!("\"{}\" is not a valid hunk address.", addr_str)),
+                    ))
+                }
+            }
+        } else if !words.is_empty() {
+            match hunks.last_mut() {
+                Some(hunk) => hunk.words.extend(words),
+                None => {
+                    return Err(SynacorErr::new_code(
+                        offset,
+                        offset + line.len(),
+                        path.to_path_buf(),
+                        text.clone(),
+                        ErrorKind::Patch(
+                            "Instructions before the first @<addr> hunk header.".to_string(),
+                        ),
+                    ))
+                }
+            }
+        }
+        offset += line.len() + 1;
+    }
+
+    Ok(hunks)
+}
+
+// address where the instructions decoded from `addr` first reach or pass `end`
+fn boundary(memory: &[u16], mut addr: usize, end: usize) -> Result<usize, SynacorErr> {
+    while addr < end {
+        match Instruction::decode(memory, addr) {
+            Some(ins) => addr = ins.next_addr(),
+            None => {
+                return Err(SynacorErr::new_addr(
+                    addr,
+                    ErrorKind::Patch(format!("No valid instruction at {:#06x}.", addr)),
+                ))
+            }
+        }
+    }
+    Ok(addr)
+}
+
+/// Write a hunk into memory, checking that both the replaced code and the replacement
+/// end on an instruction boundary unless `force` is set
+pub fn apply(memory: &mut [u16], hunk: &Hunk, force: bool) -> Result<(), SynacorErr> {
+    let end = hunk.addr + hunk.words.len();
+
+    if end > memory.len() {
+        return Err(SynacorErr::new_addr(
+            hunk.addr,
+            ErrorKind::Patch(format!(
+                "Patch of {} values runs past the end of memory at {:#06x}.",
+                hunk.words.len(),
+                memory.len()
+            )),
+        ));
+    }
+
+    if !force {
+        let replaced = boundary(memory, hunk.addr, end)?;
+        if replaced != end {
+            return Err(SynacorErr::new_addr(
+                hunk.addr,
+                ErrorKind::Patch(format!(
+                    "Patch ends at {:#06x}, inside the instruction ending at {:#06x}. \
+                     Pad it with {} noop.",
+                    end,
+                    replaced,
+                    replaced - end
+                )),
+            ));
+        }
+
+        match boundary(&hunk.words, 0, hunk.words.len()) {
+            Ok(len) if len == hunk.words.len() => (),
+            _ => {
+                return Err(SynacorErr::new_addr(
+                    hunk.addr,
+                    ErrorKind::Patch("Patch is not made of whole instructions.".to_string()),
+                ))
+            }
+        }
+    }
+
+    memory[hunk.addr..end].copy_from_slice(&hunk.words);
+    Ok(())
+}
@@ -0,0 +1,133 @@
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::FileHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+
+use crate::error::{ErrorKind, SynacorErr};
+
+// A line-editing prompt for the game, with history kept in a file between runs. Tab
+// completes game verbs and the words of what the game printed most recently, so items and
+// exits can be completed from the room description.
+
+const VERBS: [&str; 8] = ["take", "use", "look", "go", "drop", "inv", "help", "admin"];
+/// How much of the latest output words are completed from
+pub const RECENT_LEN: usize = 4096;
+
+/// Words of game text worth completing, lowercased and at least three letters long
+pub fn words(text: &str) -> BTreeSet<String> {
+    text.split(|c: char| !c.is_ascii_alphabetic())
+        .filter(|word| word.len() >= 3)
+        .map(|word| word.to_ascii_lowercase())
+        .collect()
+}
+
+/// Candidates for the word ending at `pos`, and where that word starts
+pub fn complete(line: &str, pos: usize, words: &BTreeSet<String>) -> (usize, Vec<String>) {
+    let start = line[..pos].rfind(' ').map_or(0, |space| space + 1);
+    let prefix = &line[start..pos];
+    let candidates = words
+        .iter()
+        .filter(|word| word.starts_with(prefix) && word.as_str() != prefix)
+        .cloned()
+        .collect();
+    (start, candidates)
+}
+
+struct GameHelper {
+    words: BTreeSet<String>,
+}
+
+impl Completer for GameHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(complete(line, pos, &self.words))
+    }
+}
+
+impl Hinter for GameHelper {
+    type Hint = String;
+}
+
+impl Highlighter for GameHelper {}
+
+impl Validator for GameHelper {}
+
+impl Helper for GameHelper {}
+
+impl std::fmt::Debug for GameHelper {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "GameHelper({} words)", self.words.len())
+    }
+}
+
+fn readline_err(e: ReadlineError) -> SynacorErr {
+    SynacorErr::new_io(ErrorKind::Io(e.to_string()))
+}
+
+/// Shared handle to the line editor, so a VM holding it can still be cloned
+#[derive(Debug, Clone)]
+pub struct Prompt {
+    editor: Rc<RefCell<Editor<GameHelper, FileHistory>>>,
+    history: PathBuf,
+}
+
+impl Prompt {
+    /// Editor with the history in `history`, which is created on the first line entered
+    pub fn new(history: &Path) -> Result<Self, SynacorErr> {
+        let mut editor = Editor::new().map_err(readline_err)?;
+        editor.set_helper(Some(GameHelper {
+            words: BTreeSet::new(),
+        }));
+        if history.exists() {
+            editor.load_history(history).map_err(readline_err)?;
+        }
+
+        Ok(Self {
+            editor: Rc::new(RefCell::new(editor)),
+            history: history.to_path_buf(),
+        })
+    }
+
+    /// `$HOME/.synacor_history`, or the current directory without a home
+    pub fn default_history() -> PathBuf {
+        let home = std::env::var_os("HOME").map(PathBuf::from);
+        home.unwrap_or_default().join(".synacor_history")
+    }
+
+    /// Read a line ending in a newline, or an empty string once input ends or is interrupted
+    pub fn read_line(&self, prompt: &str, recent: &str) -> Result<String, SynacorErr> {
+        let mut editor = self.editor.borrow_mut();
+        if let Some(helper) = editor.helper_mut() {
+            helper.words = words(recent);
+            helper
+                .words
+                .extend(VERBS.iter().map(|verb| verb.to_string()));
+        }
+
+        match editor.readline(prompt) {
+            Ok(line) => {
+                if !line.trim().is_empty() {
+                    editor.add_history_entry(&line).map_err(readline_err)?;
+                    editor.save_history(&self.history).map_err(readline_err)?;
+                }
+                Ok(line + "\n")
+            }
+            Err(ReadlineError::Eof | ReadlineError::Interrupted) => Ok(String::new()),
+            Err(e) => Err(readline_err(e)),
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::cli::parse_addr;
+use crate::error::{ErrorKind, SynacorErr};
+
+// Scripts feed lines of input to the game in place of the player. Blank lines and
+// everything after a `#` are ignored, and lines starting with `!` are directives:
+//
+// !delay 50        milliseconds between typed characters, 0 to type lines at once
+// !pause           hand input to the player until they enter an empty line
+// !set $7 0x0001   set a register, hex with 0x or decimal
+// !save take.snap  write a snapshot of the VM, as it waits for the next line
+//
+// Any other line is typed into the game as it is.
+
+const DEFAULT_DELAY: Duration = Duration::from_millis(200);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Step {
+    /// A line of input, without its newline
+    Line(String),
+    Delay(Duration),
+    Pause,
+    SetRegister(usize, u16),
+    Save(PathBuf),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Script {
+    pub steps: VecDeque<Step>,
+    /// Time taken to type each character of a line
+    pub delay: Duration,
+}
+
+impl Script {
+    pub fn parse(text: &str, path: &Path) -> Result<Self, SynacorErr> {
+        let mut steps = VecDeque::new();
+        let mut offset = 0;
+
+        for line in text.lines() {
+            let start = offset;
+            offset += line.len() + 1;
+
+            let content = match line.split_once('#') {
+                Some((head, _)) => head,
+                None => line,
+            }
+            .trim();
+            if content.is_empty() {
+                continue;
+            }
+            let Some(directive) = content.strip_prefix('!') else {
+                steps.push_back(Step::Line(content.to_string()));
+                continue;
+            };
+
+            let err = |expected| {
+                let at = start + (content.as_ptr() as usize - line.as_ptr() as usize);
+                SynacorErr::new_code(
+                    at,
+                    at + content.len(),
+                    path.to_path_buf(),
+                    text.to_string(),
+                    ErrorKind::Parse {
+                        lexeme: content.to_string(),
+                        expected,
+                    },
+                )
+            };
+
+            let words: Vec<&str> = directive.split_whitespace().collect();
+            let step = match words.as_slice() {
+                ["delay", ms] => match ms.parse() {
+                    Ok(ms) => Step::Delay(Duration::from_millis(ms)),
+                    Err(_) => return Err(err("a delay in milliseconds")),
+                },
+                ["pause"] => Step::Pause,
+                ["set", reg, val] => {
+                    let reg = reg.strip_prefix('$').and_then(|reg| reg.parse().ok());
+                    match (reg, parse_addr(val)) {
+                        (Some(reg), Ok(val)) if reg < 8 && val < 32768 => {
+                            Step::SetRegister(reg, val as u16)
+                        }
+                        _ => return Err(err("a register from $0 to $7 and a 15-bit value")),
+                    }
+                }
+                ["save", file] => Step::Save(PathBuf::from(file)),
+                _ => return Err(err("!delay, !pause, !set or !save")),
+            };
+            steps.push_back(step);
+        }
+
+        Ok(Self {
+            steps,
+            delay: DEFAULT_DELAY,
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, SynacorErr> {
+        Self::parse(&std::fs::read_to_string(path)?, path)
+    }
+
+    /// The walkthrough played by `run --auto`, up to the teleporter
+    pub fn solution() -> Self {
+        Self {
+            steps: SOLUTION
+                .iter()
+                .map(|line| Step::Line(line.to_string()))
+                .collect(),
+            delay: DEFAULT_DELAY,
+        }
+    }
+}
+
+const SOLUTION: &[&str] = &[
+    "take tablet",
+    "use tablet",
+    "doorway",
+    "north",
+    "north",
+    "bridge",
+    "continue",
+    "down",
+    "east",
+    "take empty lantern",
+    "west",
+    "west",
+    "passage",
+    "ladder",
+    "west",
+    "north",
+    "south",
+    "north",
+    "take can",
+    "use can",
+    "use lantern",
+    "west",
+    "ladder",
+    "darkness",
+    "continue",
+    "west",
+    "west",
+    "west",
+    "west",
+    "north",
+    "take red coin",
+    "north",
+    "east",
+    "take concave coin",
+    "down",
+    "take corroded coin",
+    "up",
+    "west",
+    "west",
+    "take blue coin",
+    "up",
+    "take shiny coin",
+    "down",
+    "east",
+    "use blue coin",
+    "use red coin",
+    "use shiny coin",
+    "use concave coin",
+    "use corroded coin",
+    "north",
+    "take teleporter",
+    "use teleporter",
+];
@@ -0,0 +1,150 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::error::{ErrorKind, SynacorErr};
+
+// Session logs hold one event per line, each with the number of instructions run so far and
+// an FNV-1a hash of everything printed so far:
+//
+// input 662315 0x6c2f8a0ae5e6d0f1 "take tablet\n"
+// halt 667392 0x1d5ab13e9c6a77e2
+//
+// Input lines are JSON strings, so they can hold any text the player typed.
+
+const HEADER: &str = "# synacor session: instruction count, output hash, input";
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/// Running hash of program output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputHash(pub u64);
+
+impl Default for OutputHash {
+    fn default() -> Self {
+        Self(FNV_OFFSET)
+    }
+}
+
+impl OutputHash {
+    pub fn update(&mut self, byte: u8) {
+        self.0 = (self.0 ^ byte as u64).wrapping_mul(FNV_PRIME);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// A line handed to the program, or to the admin console
+    Input {
+        count: u64,
+        hash: OutputHash,
+        line: String,
+    },
+    Halt {
+        count: u64,
+        hash: OutputHash,
+    },
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Input { count, hash, line } => write!(
+                f,
+                "input {} {:#018x} {}",
+                count,
+                hash.0,
+                serde_json::Value::from(line.as_str())
+            ),
+            Self::Halt { count, hash } => write!(f, "halt {} {:#018x}", count, hash.0),
+        }
+    }
+}
+
+/// Appends events to a session log as they happen, so that a crash keeps everything up to it
+#[derive(Debug, Clone)]
+pub struct Recorder {
+    path: PathBuf,
+}
+
+impl Recorder {
+    pub fn create(path: &Path) -> Result<Self, SynacorErr> {
+        std::fs::write(path, format!("{}\n", HEADER))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+        })
+    }
+
+    pub fn record(&self, event: &Event) -> Result<(), SynacorErr> {
+        let mut file = OpenOptions::new().append(true).open(&self.path)?;
+        writeln!(file, "{}", event)?;
+        Ok(())
+    }
+}
+
+/// Events of a recorded session, in order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Replay {
+    pub events: VecDeque<Event>,
+}
+
+fn parse_hash(word: &str) -> Option<OutputHash> {
+    u64::from_str_radix(word.strip_prefix("0x")?, 16)
+        .ok()
+        .map(OutputHash)
+}
+
+impl Replay {
+    pub fn parse(text: &str, path: &Path) -> Result<Self, SynacorErr> {
+        let mut events = VecDeque::new();
+        let mut offset = 0;
+
+        for line in text.lines() {
+            let start = offset;
+            offset += line.len() + 1;
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut words = line.splitn(4, ' ');
+            let (kind, count, hash) = (words.next(), words.next(), words.next());
+            let count = count.and_then(|count| count.parse().ok());
+            let hash = hash.and_then(parse_hash);
+
+            let event = match (kind, count, hash, words.next()) {
+                (Some("input"), Some(count), Some(hash), Some(json)) => {
+                    match serde_json::from_str(json) {
+                        Ok(line) => Some(Event::Input { count, hash, line }),
+                        Err(_) => None,
+                    }
+                }
+                (Some("halt"), Some(count), Some(hash), None) => Some(Event::Halt { count, hash }),
+                _ => None,
+            };
+
+            match event {
+                Some(event) => events.push_back(event),
+                None => {
+                    return Err(SynacorErr::new_code(
+                        start,
+                        start + line.len(),
+                        path.to_path_buf(),
+                        text.to_string(),
+                        ErrorKind::Parse {
+                            lexeme: line.to_string(),
+                            expected: "a session event",
+                        },
+                    ))
+                }
+            }
+        }
+
+        Ok(Self { events })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, SynacorErr> {
+        Self::parse(&std::fs::read_to_string(path)?, path)
+    }
+}
@@ -0,0 +1,303 @@
+use std::ops::Range;
+use std::path::Path;
+
+use itertools::Itertools;
+
+use crate::error::{ErrorKind, SynacorErr};
+use crate::strings::{decrypted_image, find_strings};
+
+// Slot puzzles ask for items to be placed in the blanks of an equation, each item standing
+// for a number, like the monument in the ruins:
+//
+// _ + _ * _^2 + _^3 - _ = 399
+//
+// Equations have blanks, integers, `+`, `-`, `*`, `^` and parentheses, with the usual
+// precedence. Blanks are filled left to right, so a solution is the order to use items in.
+// The numbers come from item descriptions, such as "It has seven dots on one side." or
+// "It has a pentagon on one side.".
+
+const NUMBERS: [&str; 10] = [
+    "one", "two", "three", "four", "five", "six", "seven", "eight", "nine", "ten",
+];
+const SHAPES: [(&str, i64); 7] = [
+    ("triangle", 3),
+    ("square", 4),
+    ("pentagon", 5),
+    ("hexagon", 6),
+    ("heptagon", 7),
+    ("octagon", 8),
+    ("nonagon", 9),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expr {
+    Slot,
+    Num(i64),
+    Binary(char, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Tok {
+    Slot,
+    Num(i64),
+    Op(char),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Item {
+    pub name: String,
+    pub value: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Equation {
+    text: String,
+    lhs: Expr,
+    pub target: i64,
+    pub slots: usize,
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    path: &'a Path,
+    tokens: Vec<(Tok, Range<usize>)>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn err(&self, span: Range<usize>, expected: &'static str) -> SynacorErr {
+        SynacorErr::new_code(
+            span.start,
+            span.end,
+            self.path.to_path_buf(),
+            self.text.to_string(),
+            ErrorKind::Parse {
+                lexeme: self.text[span].to_string(),
+                expected,
+            },
+        )
+    }
+
+    fn lex(text: &'a str, path: &'a Path) -> Result<Self, SynacorErr> {
+        let mut parser = Self {
+            text,
+            path,
+            tokens: Vec::new(),
+            pos: 0,
+        };
+
+        let mut chars = text.char_indices().peekable();
+        while let Some((start, c)) = chars.next() {
+            let tok = match c {
+                ' ' | '\t' => continue,
+                '_' => Tok::Slot,
+                '+' | '-' | '*' | '^' | '(' | ')' | '=' => Tok::Op(c),
+                '0'..='9' => {
+                    let mut end = start + 1;
+                    while let Some((at, '0'..='9')) = chars.peek() {
+                        end = at + 1;
+                        chars.next();
+                    }
+                    match text[start..end].parse() {
+                        Ok(num) => Tok::Num(num),
+                        Err(_) => return Err(parser.err(start..end, "a number")),
+                    }
+                }
+                _ => return Err(parser.err(start..start + c.len_utf8(), "part of an equation")),
+            };
+            let end = chars.peek().map_or(text.len(), |(at, _)| *at);
+            parser.tokens.push((tok, start..end));
+        }
+        Ok(parser)
+    }
+
+    fn peek(&self) -> Option<&Tok> {
+        self.tokens.get(self.pos).map(|(tok, _)| tok)
+    }
+
+    fn next(&mut self, expected: &'static str) -> Result<Tok, SynacorErr> {
+        match self.tokens.get(self.pos) {
+            Some((tok, _)) => {
+                self.pos += 1;
+                Ok(tok.clone())
+            }
+            None => Err(self.err(self.text.len()..self.text.len(), expected)),
+        }
+    }
+
+    fn unexpected(&self, expected: &'static str) -> SynacorErr {
+        let span = self.tokens[self.pos - 1].1.clone();
+        self.err(span, expected)
+    }
+
+    fn binary(
+        &mut self,
+        ops: &[char],
+        operand: fn(&mut Self) -> Result<Expr, SynacorErr>,
+    ) -> Result<Expr, SynacorErr> {
+        let mut lhs = operand(self)?;
+        while let Some(Tok::Op(op)) = self.peek().cloned() {
+            if !ops.contains(&op) {
+                break;
+            }
+            self.pos += 1;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(operand(self)?));
+        }
+        Ok(lhs)
+    }
+
+    fn sum(&mut self) -> Result<Expr, SynacorErr> {
+        self.binary(&['+', '-'], Self::product)
+    }
+
+    fn product(&mut self) -> Result<Expr, SynacorErr> {
+        self.binary(&['*'], Self::power)
+    }
+
+    fn power(&mut self) -> Result<Expr, SynacorErr> {
+        let base = self.atom()?;
+        if self.peek() != Some(&Tok::Op('^')) {
+            return Ok(base);
+        }
+        self.pos += 1;
+        Ok(Expr::Binary('^', Box::new(base), Box::new(self.power()?)))
+    }
+
+    fn atom(&mut self) -> Result<Expr, SynacorErr> {
+        match self.next("a blank, number or `(`")? {
+            Tok::Slot => Ok(Expr::Slot),
+            Tok::Num(num) => Ok(Expr::Num(num)),
+            Tok::Op('(') => {
+                let expr = self.sum()?;
+                match self.next("`)`")? {
+                    Tok::Op(')') => Ok(expr),
+                    _ => Err(self.unexpected("`)`")),
+                }
+            }
+            _ => Err(self.unexpected("a blank, number or `(`")),
+        }
+    }
+}
+
+impl Expr {
+    fn slots(&self) -> usize {
+        match self {
+            Self::Slot => 1,
+            Self::Num(_) => 0,
+            Self::Binary(_, lhs, rhs) => lhs.slots() + rhs.slots(),
+        }
+    }
+
+    // `None` on overflow or a negative power
+    fn eval(&self, values: &mut impl Iterator<Item = i64>) -> Option<i64> {
+        match self {
+            Self::Slot => values.next(),
+            Self::Num(num) => Some(*num),
+            Self::Binary(op, lhs, rhs) => {
+                let (a, b) = (lhs.eval(values)?, rhs.eval(values)?);
+                match op {
+                    '+' => a.checked_add(b),
+                    '-' => a.checked_sub(b),
+                    '*' => a.checked_mul(b),
+                    _ => a.checked_pow(b.try_into().ok()?),
+                }
+            }
+        }
+    }
+}
+
+impl Equation {
+    pub fn parse(text: &str, path: &Path) -> Result<Self, SynacorErr> {
+        let mut parser = Parser::lex(text, path)?;
+        let lhs = parser.sum()?;
+
+        match parser.next("`=`")? {
+            Tok::Op('=') => (),
+            _ => return Err(parser.unexpected("`=`")),
+        }
+        let target = match parser.next("a number")? {
+            Tok::Num(num) => num,
+            _ => return Err(parser.unexpected("a number")),
+        };
+        if parser.peek().is_some() {
+            parser.pos += 1;
+            return Err(parser.unexpected("the end of the equation"));
+        }
+
+        Ok(Self {
+            text: text.trim().to_string(),
+            slots: lhs.slots(),
+            lhs,
+            target,
+        })
+    }
+
+    /// Whether the values, in blank order, satisfy the equation
+    pub fn holds(&self, values: &[i64]) -> bool {
+        values.len() == self.slots
+            && self.lhs.eval(&mut values.iter().copied()) == Some(self.target)
+    }
+
+    /// The first order of distinct items that satisfies the equation
+    pub fn solve<'a>(&self, items: &'a [Item]) -> Option<Vec<&'a Item>> {
+        items.iter().permutations(self.slots).find(|order| {
+            let values: Vec<i64> = order.iter().map(|item| item.value).collect();
+            self.holds(&values)
+        })
+    }
+
+    /// The equation with its blanks filled in
+    pub fn fill(&self, values: &[i64]) -> String {
+        let mut values = values.iter();
+        self.text
+            .chars()
+            .map(|c| match c {
+                '_' => values.next().map_or(c.to_string(), |val| val.to_string()),
+                _ => c.to_string(),
+            })
+            .collect()
+    }
+}
+
+/// The number shown on an item, from a description such as "It has two dots on one side."
+pub fn value_of(description: &str) -> Option<i64> {
+    description
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .find_map(|word| {
+            let word = word.to_ascii_lowercase();
+            let number = NUMBERS.iter().position(|number| *number == word);
+            let shape = SHAPES.iter().find(|(shape, _)| *shape == word);
+            match (number, shape) {
+                (Some(at), _) => Some(at as i64 + 1),
+                (_, Some((_, sides))) => Some(*sides),
+                _ => word.parse().ok(),
+            }
+        })
+}
+
+/// The first equation among the program's strings, and every item whose name ends in `kind`
+/// valued by the description stored after it
+pub fn scrape(memory: Vec<u16>, kind: &str) -> Result<(Option<Equation>, Vec<Item>), SynacorErr> {
+    let decrypted = decrypted_image(memory)?;
+    let strings = find_strings(&decrypted, 0..decrypted.len(), kind.len());
+
+    let equation = strings
+        .iter()
+        .flat_map(|string| string.text.lines())
+        .filter(|line| line.contains('_') && line.contains('='))
+        .find_map(|line| Equation::parse(line, Path::new("<program>")).ok());
+
+    let items = strings
+        .iter()
+        .tuple_windows()
+        .filter(|(name, _)| name.text.ends_with(kind))
+        .filter_map(|(name, description)| {
+            Some(Item {
+                name: name.text.clone(),
+                value: value_of(&description.text)?,
+            })
+        })
+        .collect();
+
+    Ok((equation, items))
+}
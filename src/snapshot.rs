@@ -0,0 +1,86 @@
+use std::path::Path;
+
+use itertools::Itertools;
+
+use crate::error::{ErrorKind, SynacorErr};
+
+const MAGIC: &[u8; 8] = b"SYNSNAP\0";
+
+/// Machine state to resume a run from: memory, registers, stack and address. Input queued
+/// for a headless VM is not kept, and is discarded when restoring
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub memory: Vec<u16>,
+    pub registers: [u16; 8],
+    pub stack: Vec<u16>,
+    pub addr: usize,
+}
+
+// Snapshot files are the magic bytes followed by little-endian values, as in program binaries:
+// address, eight registers, stack length (two values, low first), stack, then memory.
+
+impl Snapshot {
+    pub fn is_snapshot(bytes: &[u8]) -> bool {
+        bytes.starts_with(MAGIC)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let len = self.stack.len() as u32;
+        let values = [self.addr as u16]
+            .iter()
+            .chain(self.registers.iter())
+            .chain([len as u16, (len >> 16) as u16].iter())
+            .chain(self.stack.iter())
+            .chain(self.memory.iter())
+            .flat_map(|x| u16::to_le_bytes(*x))
+            .collect::<Vec<u8>>();
+
+        [MAGIC.as_slice(), &values].concat()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SynacorErr> {
+        let Some(body) = bytes.strip_prefix(MAGIC.as_slice()) else {
+            return Err(SynacorErr::new_plain(ErrorKind::Snapshot(
+                "Not a snapshot file, the header is missing.".to_string(),
+            )));
+        };
+
+        let values: Vec<u16> = body
+            .iter()
+            .tuples()
+            .map(|(low, high)| u16::from_le_bytes([*low, *high]))
+            .collect();
+
+        if values.len() < 11 {
+            return Err(SynacorErr::new_plain(ErrorKind::Snapshot(
+                "Snapshot is truncated.".to_string(),
+            )));
+        }
+
+        let len = (values[9] as usize) | ((values[10] as usize) << 16);
+        let Some(stack) = values.get(11..11 + len) else {
+            return Err(SynacorErr::new_plain(ErrorKind::Snapshot(
+                "Snapshot is truncated.".to_string(),
+            )));
+        };
+
+        let mut registers = [0; 8];
+        registers.copy_from_slice(&values[1..9]);
+
+        Ok(Self {
+            addr: values[0] as usize,
+            registers,
+            stack: stack.to_vec(),
+            memory: values[11 + len..].to_vec(),
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), SynacorErr> {
+        std::fs::write(path, self.to_bytes())?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, SynacorErr> {
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+}
@@ -0,0 +1,304 @@
+use std::collections::{HashSet, VecDeque};
+use std::path::Path;
+
+use crate::error::{ErrorKind, SynacorErr};
+use crate::snapshot::Snapshot;
+use crate::vm::{State, VM};
+use crate::world::{parse_room, Room};
+
+// The solver searches breadth-first for the fewest commands after which the game prints the
+// goal text. A state is the room as `look` describes it, which covers items on the floor and
+// text that changes as puzzles are worked on, along with the inventory, and the value at the
+// room address. Every state tries each exit, taking each item in the room and using each item
+// carried.
+//
+// Rooms can read the same, as in a maze, so without a room address given one is looked for in
+// memory: the lowest word that changes on every move out of the starting room into one that
+// reads differently, and that stays put when only looking around. Without one, rooms that read
+// the same are taken for one.
+//
+// After every command, `look` and `inv` are typed to learn the state it led to. They are
+// left out of the solution, which is written as a script for `run --script`, unless the goal
+// only shows in what they print.
+
+/// Items listed in the last inventory shown, or `None` without one
+pub fn parse_inventory(output: &str) -> Option<Vec<String>> {
+    let start = output.rfind("Your inventory:")?;
+    let items = output[start..]
+        .lines()
+        .skip(1)
+        .map_while(|line| line.strip_prefix("- "))
+        .map(|item| item.to_string())
+        .collect();
+    Some(items)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Key {
+    room: Room,
+    room_value: Option<u16>,
+    inventory: Vec<String>,
+}
+
+/// A state kept as the memory it changed from the starting one, as there can be thousands
+struct Saved {
+    changes: Vec<(usize, u16)>,
+    registers: [u16; 8],
+    stack: Vec<u16>,
+    addr: usize,
+}
+
+impl Saved {
+    fn new(vm: &VM, base: &[u16]) -> Self {
+        let changes = vm
+            .memory()
+            .iter()
+            .enumerate()
+            .filter(|(addr, now)| base.get(*addr).unwrap_or(&0) != *now)
+            .map(|(addr, now)| (addr, *now))
+            .collect();
+        Self {
+            changes,
+            registers: *vm.registers(),
+            stack: vm.stack().to_vec(),
+            addr: vm.addr(),
+        }
+    }
+
+    fn restore(&self, vm: &mut VM, base: &[u16]) {
+        let mut memory = base.to_vec();
+        memory.resize(vm.memory().len(), 0);
+        for (addr, val) in self.changes.iter() {
+            memory[*addr] = *val;
+        }
+        vm.restore(&Snapshot {
+            memory,
+            registers: self.registers,
+            stack: self.stack.clone(),
+            addr: self.addr,
+        });
+        // output left over from a command that ended the game
+        vm.take_output();
+    }
+}
+
+/// Commands reaching the goal, and how many states were seen on the way
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Solution {
+    pub commands: Vec<String>,
+    pub states: usize,
+}
+
+impl Solution {
+    /// The commands as a script, one per line
+    pub fn to_script(&self, goal: &str) -> String {
+        let mut script = format!(
+            "# {} commands until the game prints \"{}\"\n",
+            self.commands.len(),
+            goal
+        );
+        for command in self.commands.iter() {
+            script.push_str(command);
+            script.push('\n');
+        }
+        script
+    }
+}
+
+/// Commands typed after every other one to learn the state it led to
+const OBSERVE: [&str; 2] = ["look", "inv"];
+
+/// What each of `OBSERVE` printed, and the state the game is in, or `None` when it stopped
+/// making sense
+fn observe(
+    vm: &mut VM,
+    room_addr: Option<usize>,
+) -> Result<(Vec<String>, Option<Key>), SynacorErr> {
+    let mut printed = Vec::new();
+    for command in OBSERVE {
+        vm.feed(&format!("{}\n", command));
+        let state = vm.run()?;
+        printed.push(vm.take_output());
+        if state != State::AwaitingInput {
+            return Ok((printed, None));
+        }
+    }
+
+    let (Some(room), Some(inventory)) = (parse_room(&printed[0]), parse_inventory(&printed[1]))
+    else {
+        return Ok((printed, None));
+    };
+    let key = Key {
+        room,
+        room_value: room_addr.map(|addr| vm.memory()[addr]),
+        inventory,
+    };
+    Ok((printed, Some(key)))
+}
+
+/// Look in memory for the address holding the current room, trying each exit of the `start`
+/// state the program is in. The program is left in that state.
+fn find_room_addr(vm: &mut VM, start: &Key) -> Result<Option<usize>, SynacorErr> {
+    let base = vm.snapshot();
+    let differs = |vm: &VM| -> HashSet<usize> {
+        (0..vm.memory().len())
+            .filter(|addr| vm.memory()[*addr] != base.memory[*addr])
+            .collect()
+    };
+
+    observe(vm, None)?;
+    let looking = differs(vm);
+
+    let mut found: Option<HashSet<usize>> = None;
+    for exit in start.room.exits.iter() {
+        vm.restore(&base);
+        vm.feed(&format!("{}\n", exit));
+        if vm.run()? != State::AwaitingInput {
+            continue;
+        }
+        let Some(next) = observe(vm, None)?.1 else {
+            continue;
+        };
+        if next.room == start.room {
+            continue;
+        }
+
+        let moved = differs(vm);
+        found = Some(match found {
+            Some(found) => found.intersection(&moved).copied().collect(),
+            None => moved,
+        });
+    }
+
+    vm.restore(&base);
+    vm.take_output();
+    Ok(found.and_then(|found| found.difference(&looking).copied().min()))
+}
+
+/// Find the fewest commands after which the program prints `goal`, giving up after
+/// `max_states` states
+pub fn solve(
+    memory: Vec<u16>,
+    goal: &str,
+    mut room_addr: Option<usize>,
+    max_states: usize,
+) -> Result<Solution, SynacorErr> {
+    let base = memory.clone();
+    let mut vm = VM::headless(memory);
+    let unsolved = |states| {
+        SynacorErr::new_plain(ErrorKind::Solve(format!(
+            "No commands make the program print \"{}\", after {} states.",
+            goal, states
+        )))
+    };
+
+    if vm.run()? != State::AwaitingInput {
+        return Err(unsolved(0));
+    }
+    if vm.take_output().contains(goal) {
+        return Ok(Solution {
+            commands: Vec::new(),
+            states: 0,
+        });
+    }
+    let (printed, start) = observe(&mut vm, room_addr)?;
+    if let Some(found) = printed.iter().position(|output| output.contains(goal)) {
+        return Ok(Solution {
+            commands: vec![OBSERVE[found].to_string()],
+            states: 0,
+        });
+    }
+    let Some(mut start) = start else {
+        return Err(unsolved(0));
+    };
+    if room_addr.is_none() {
+        if let Some(addr) = find_room_addr(&mut vm, &start)? {
+            room_addr = Some(addr);
+            start.room_value = Some(vm.memory()[addr]);
+        }
+    }
+
+    // every state seen, by the state it was reached from and the command reaching it
+    let mut paths: Vec<(usize, String)> = vec![(0, String::new())];
+    let mut seen: HashSet<Key> = HashSet::new();
+    let mut todo: VecDeque<(usize, Key, Saved)> = VecDeque::new();
+    todo.push_back((0, start.clone(), Saved::new(&vm, &base)));
+    seen.insert(start);
+
+    while let Some((from, key, saved)) = todo.pop_front() {
+        let commands = key
+            .room
+            .exits
+            .iter()
+            .cloned()
+            .chain(key.room.items.iter().map(|item| format!("take {}", item)))
+            .chain(key.inventory.iter().map(|item| format!("use {}", item)));
+
+        for command in commands {
+            saved.restore(&mut vm, &base);
+            vm.feed(&format!("{}\n", command));
+            if vm.run()? != State::AwaitingInput {
+                continue;
+            }
+
+            // the goal shows after the command, or only in what observing it printed
+            let (found, next) = if vm.take_output().contains(goal) {
+                (Some(None), None)
+            } else {
+                let (printed, next) = observe(&mut vm, room_addr)?;
+                let found = printed.iter().position(|output| output.contains(goal));
+                (found.map(Some), next)
+            };
+            if let Some(observed) = found {
+                let mut commands = vec![command];
+                let mut at = from;
+                while at != 0 {
+                    commands.push(paths[at].1.clone());
+                    at = paths[at].0;
+                }
+                commands.reverse();
+                commands.extend(observed.map(|found| OBSERVE[found].to_string()));
+                return Ok(Solution {
+                    commands,
+                    states: paths.len(),
+                });
+            }
+
+            let Some(next) = next else {
+                continue;
+            };
+            if seen.contains(&next) {
+                continue;
+            }
+            if paths.len() == max_states {
+                return Err(unsolved(max_states));
+            }
+
+            paths.push((from, command));
+            todo.push_back((paths.len() - 1, next.clone(), Saved::new(&vm, &base)));
+            seen.insert(next);
+        }
+    }
+
+    Err(unsolved(paths.len()))
+}
+
+/// Solve the program and write the commands as a script
+pub fn write_solution(
+    memory: Vec<u16>,
+    goal: &str,
+    room_addr: Option<usize>,
+    max_states: usize,
+    out_path: &Path,
+) -> Result<(), SynacorErr> {
+    let solution = solve(memory, goal, room_addr, max_states)?;
+    println!(
+        "Solved in {} commands after {} states",
+        solution.commands.len(),
+        solution.states
+    );
+
+    std::fs::write(out_path, solution.to_script(goal))?;
+    println!("Created script file {}", out_path.display());
+    Ok(())
+}
@@ -0,0 +1,87 @@
+use std::ops::Range;
+
+use crate::error::SynacorErr;
+use crate::vm::VM;
+
+/// A length-prefixed string found in memory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedString {
+    pub addr: usize,
+    pub text: String,
+}
+
+impl DecodedString {
+    // newlines and quotes escaped so each string fits on one table row
+    pub fn escaped(&self) -> String {
+        self.text.escape_default().to_string()
+    }
+}
+
+/// Instructions a program may run before it is taken to loop forever, well beyond the million
+/// or so the challenge needs to decrypt itself
+pub const MAX_STEPS: usize = 10_000_000;
+
+/// Memory once the program has decrypted itself, which the challenge finishes before it first
+/// asks for input
+pub fn decrypted_image(memory: Vec<u16>) -> Result<Vec<u16>, SynacorErr> {
+    let len = memory.len();
+    let mut vm = VM::headless(memory);
+    vm.run_for(MAX_STEPS)?;
+
+    // keep anything the program wrote past the end of the original image
+    let used = vm
+        .memory()
+        .iter()
+        .rposition(|val| *val != 0)
+        .map_or(0, |last| last + 1);
+    Ok(vm.memory()[..len.max(used)].to_vec())
+}
+
+/// Address ranges whose contents differ between two images
+pub fn changed_ranges(original: &[u16], current: &[u16]) -> Vec<Range<usize>> {
+    let len = original.len().max(current.len());
+    let differs = |addr: usize| original.get(addr) != current.get(addr);
+
+    let mut ranges = Vec::new();
+    let mut addr = 0;
+    while addr < len {
+        if differs(addr) {
+            let start = addr;
+            while addr < len && differs(addr) {
+                addr += 1;
+            }
+            ranges.push(start..addr);
+        } else {
+            addr += 1;
+        }
+    }
+    ranges
+}
+
+fn printable(val: u16) -> bool {
+    (0x20..0x7f).contains(&val) || val == b'\n' as u16
+}
+
+/// Length-prefixed printable strings starting inside `range`
+pub fn find_strings(memory: &[u16], range: Range<usize>, min_len: usize) -> Vec<DecodedString> {
+    let mut strings = Vec::new();
+    let mut addr = range.start;
+
+    while addr < range.end.min(memory.len()) {
+        let len = memory[addr] as usize;
+        let chars = memory.get(addr + 1..addr + 1 + len);
+
+        match chars {
+            Some(chars) if len >= min_len && chars.iter().all(|val| printable(*val)) => {
+                strings.push(DecodedString {
+                    addr,
+                    text: chars.iter().map(|val| *val as u8 as char).collect(),
+                });
+                addr += len + 1;
+            }
+            _ => addr += 1,
+        }
+    }
+
+    strings
+}
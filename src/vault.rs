@@ -0,0 +1,336 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::Path;
+
+use crate::error::{ErrorKind, SynacorErr};
+use crate::snapshot::Snapshot;
+use crate::vm::{State, VM};
+use crate::world::parse_room;
+
+// The vault lock is a grid of rooms with numbers and operators on their floors. The orb starts
+// in the antechamber, with the weight carved into its pedestal, and the door takes the weight
+// carved into it:
+//
+// target 30
+// *  8  -  1
+// 4  *  11 *
+// +  4  -  18
+// 22 -  9  *
+//
+// Walking onto an operator and then onto a number applies both to the orb's weight. Walking
+// back into the antechamber resets the orb, reaching the door with the wrong weight makes it
+// evaporate, and it shatters outside 15-bit values. Grid files hold the rows from north to
+// south, with the antechamber in the bottom-left corner and the door in the top-right one.
+
+const DIRS: [(&str, isize, isize); 4] = [
+    ("north", -1, 0),
+    ("east", 0, 1),
+    ("south", 1, 0),
+    ("west", 0, -1),
+];
+const MAX_WEIGHT: i64 = 32767;
+
+/// A room of the grid and the orb's weight there
+type Orb = ((usize, usize), i64);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cell {
+    Num(i64),
+    Op(char),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Vault {
+    /// Rows from north to south
+    pub rows: Vec<Vec<Cell>>,
+    pub start: (usize, usize),
+    pub door: (usize, usize),
+    /// Weight the door opens for
+    pub target: i64,
+}
+
+fn parse_cell(word: &str) -> Option<Cell> {
+    match word {
+        "+" | "-" | "*" => word.chars().next().map(Cell::Op),
+        _ => word.parse().ok().map(Cell::Num),
+    }
+}
+
+impl Vault {
+    pub fn parse(text: &str, path: &Path) -> Result<Self, SynacorErr> {
+        let mut rows: Vec<Vec<Cell>> = Vec::new();
+        let mut target = None;
+        let mut offset = 0;
+
+        for line in text.lines() {
+            let start = offset;
+            offset += line.len() + 1;
+            let err = |word: &str, expected| {
+                let at = start + (word.as_ptr() as usize - line.as_ptr() as usize);
+                SynacorErr::new_code(
+                    at,
+                    at + word.len(),
+                    path.to_path_buf(),
+                    text.to_string(),
+                    ErrorKind::Parse {
+                        lexeme: word.to_string(),
+                        expected,
+                    },
+                )
+            };
+
+            let content = line.split('#').next().unwrap_or_default().trim();
+            let words: Vec<&str> = content.split_whitespace().collect();
+            match words.as_slice() {
+                [] => (),
+                ["target", weight] => match weight.parse() {
+                    Ok(weight) => target = Some(weight),
+                    Err(_) => return Err(err(weight, "a weight")),
+                },
+                _ => {
+                    let row = words
+                        .iter()
+                        .map(|word| {
+                            parse_cell(word).ok_or_else(|| err(word, "a number or +, - or *"))
+                        })
+                        .collect::<Result<Vec<Cell>, SynacorErr>>()?;
+                    if rows.first().is_some_and(|first| first.len() != row.len()) {
+                        return Err(err(content, "a row as wide as the first"));
+                    }
+                    rows.push(row);
+                }
+            }
+        }
+
+        let invalid = |details: &str| {
+            SynacorErr::new_plain(ErrorKind::Solve(format!("{}: {}", path.display(), details)))
+        };
+        let Some(target) = target else {
+            return Err(invalid("no `target` line with the door's weight."));
+        };
+        let Some(width) = rows.first().map(|row| row.len()) else {
+            return Err(invalid("no rows of rooms."));
+        };
+        let start = (rows.len() - 1, 0);
+        if !matches!(rows[start.0][start.1], Cell::Num(_)) {
+            return Err(invalid(
+                "the antechamber in the bottom-left corner needs a weight.",
+            ));
+        }
+
+        Ok(Self {
+            rows,
+            start,
+            door: (0, width - 1),
+            target,
+        })
+    }
+
+    fn cell(&self, (row, col): (usize, usize)) -> Cell {
+        self.rows[row][col]
+    }
+
+    fn step(&self, (row, col): (usize, usize), (dr, dc): (isize, isize)) -> Option<(usize, usize)> {
+        let row = row.checked_add_signed(dr)?;
+        let col = col.checked_add_signed(dc)?;
+        self.rows.get(row)?.get(col)?;
+        Some((row, col))
+    }
+
+    /// The fewest moves from the antechamber that reach the door with the right weight
+    pub fn solve(&self) -> Option<Vec<&'static str>> {
+        let Cell::Num(weight) = self.cell(self.start) else {
+            return None;
+        };
+
+        // states by position and weight, with the state and move they were reached by
+        let mut came_from: HashMap<Orb, (Orb, &str)> = HashMap::new();
+        let mut seen = HashSet::from([(self.start, weight)]);
+        let mut todo = VecDeque::from([(self.start, weight)]);
+
+        while let Some((pos, weight)) = todo.pop_front() {
+            for (name, dr, dc) in DIRS {
+                let Some(next) = self.step(pos, (dr, dc)) else {
+                    continue;
+                };
+                if next == self.start {
+                    continue;
+                }
+
+                let next_weight = match (self.cell(pos), self.cell(next)) {
+                    (Cell::Op(op), Cell::Num(num)) => match op {
+                        '+' => weight + num,
+                        '-' => weight - num,
+                        _ => weight * num,
+                    },
+                    _ => weight,
+                };
+                if !(0..=MAX_WEIGHT).contains(&next_weight) {
+                    continue;
+                }
+
+                let state = (next, next_weight);
+                if next == self.door && next_weight != self.target {
+                    continue;
+                }
+                if !seen.insert(state) {
+                    continue;
+                }
+                came_from.insert(state, ((pos, weight), name));
+
+                if next == self.door {
+                    let mut moves = Vec::new();
+                    let mut at = state;
+                    while let Some((prev, name)) = came_from.get(&at) {
+                        moves.push(*name);
+                        at = *prev;
+                    }
+                    moves.reverse();
+                    return Some(moves);
+                }
+                todo.push_back(state);
+            }
+        }
+        None
+    }
+
+    /// The arithmetic done to the orb along the moves, such as `22 + 4 - 11 = 15`
+    pub fn equation(&self, moves: &[&str]) -> String {
+        let mut pos = self.start;
+        let mut text = String::new();
+        let mut weight = 0;
+        let mut cells = vec![self.cell(pos)];
+        for name in moves {
+            let Some((_, dr, dc)) = DIRS.iter().find(|(dir, _, _)| dir == name) else {
+                continue;
+            };
+            let Some(next) = self.step(pos, (*dr, *dc)) else {
+                continue;
+            };
+            pos = next;
+            cells.push(self.cell(pos));
+        }
+
+        let mut op = '+';
+        for cell in cells {
+            match cell {
+                Cell::Op(next) => op = next,
+                Cell::Num(num) => {
+                    weight = match op {
+                        '+' => weight + num,
+                        '-' => weight - num,
+                        _ => weight * num,
+                    };
+                    if !text.is_empty() {
+                        text.push_str(&format!(" {} ", op));
+                    }
+                    text.push_str(&num.to_string());
+                }
+            }
+        }
+        format!("{} = {}", text, weight)
+    }
+}
+
+// the text inside the quotes following `phrase`
+fn quoted_after<'a>(text: &'a str, phrase: &str) -> Option<&'a str> {
+    let rest = &text[text.find(phrase)? + phrase.len()..];
+    let rest = &rest[rest.find('\'')? + 1..];
+    Some(&rest[..rest.find('\'')?])
+}
+
+fn read_cell(description: &str) -> Option<Cell> {
+    quoted_after(description, "depicting the number")
+        .or_else(|| quoted_after(description, "depicting a"))
+        .or_else(|| quoted_after(description, "You notice the number"))
+        .and_then(parse_cell)
+}
+
+/// Read the grid by walking its rooms, from a snapshot taken in the antechamber
+pub fn scrape(snapshot: &Snapshot) -> Result<Vault, SynacorErr> {
+    let mut vm = VM::headless(Vec::new());
+    let unreadable = |details: &str| SynacorErr::new_plain(ErrorKind::Solve(details.to_string()));
+
+    // the room reached by walking a path from the snapshot
+    let mut walk = |path: &[&str]| -> Result<Option<(String, Vec<String>)>, SynacorErr> {
+        vm.restore(snapshot);
+        // output left over from a walk that ended the game
+        vm.take_output();
+        vm.feed("look\n");
+        for name in path {
+            vm.feed(&format!("{}\n", name));
+        }
+        if vm.run()? != State::AwaitingInput {
+            return Ok(None);
+        }
+        let output = vm.take_output();
+        Ok(parse_room(&output).map(|room| (room.description, room.exits)))
+    };
+
+    let Some((description, _)) = walk(&[])? else {
+        return Err(unreadable(
+            "The snapshot is not waiting for input in a room.",
+        ));
+    };
+    if !description.contains("orb's pedestal") {
+        return Err(unreadable(
+            "The snapshot is not in the vault antechamber, by the orb's pedestal.",
+        ));
+    }
+
+    let mut cells: HashMap<(isize, isize), Cell> = HashMap::new();
+    let mut paths: HashMap<(isize, isize), Vec<&str>> = HashMap::from([((0, 0), Vec::new())]);
+    let mut todo = VecDeque::from([(0, 0)]);
+    let mut target = None;
+
+    while let Some(pos) = todo.pop_front() {
+        let path = paths[&pos].clone();
+        let Some((description, exits)) = walk(&path)? else {
+            continue;
+        };
+        let Some(cell) = read_cell(&description) else {
+            continue;
+        };
+        cells.insert(pos, cell);
+        if let Some(weight) = quoted_after(&description, "it has a large") {
+            target = weight.parse().ok().map(|weight| (pos, weight));
+        }
+
+        for (name, dr, dc) in DIRS {
+            let next = (pos.0 + dr, pos.1 + dc);
+            if exits.iter().any(|exit| exit == name) && !paths.contains_key(&next) {
+                let mut next_path = path.clone();
+                next_path.push(name);
+                paths.insert(next, next_path);
+                todo.push_back(next);
+            }
+        }
+    }
+
+    let Some((door, target)) = target else {
+        return Err(unreadable("No vault door with a weight carved into it."));
+    };
+    let top = cells.keys().map(|pos| pos.0).min().unwrap_or_default();
+    let left = cells.keys().map(|pos| pos.1).min().unwrap_or_default();
+    let bottom = cells.keys().map(|pos| pos.0).max().unwrap_or_default();
+    let right = cells.keys().map(|pos| pos.1).max().unwrap_or_default();
+
+    let mut rows = Vec::new();
+    for row in top..=bottom {
+        let mut cols = Vec::new();
+        for col in left..=right {
+            match cells.get(&(row, col)) {
+                Some(cell) => cols.push(*cell),
+                None => return Err(unreadable("The vault lock is not a full grid of rooms.")),
+            }
+        }
+        rows.push(cols);
+    }
+
+    let index = |(row, col): (isize, isize)| ((row - top) as usize, (col - left) as usize);
+    Ok(Vault {
+        rows,
+        start: index((0, 0)),
+        door: index(door),
+        target,
+    })
+}
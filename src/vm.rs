@@ -1,20 +1,68 @@
 use std::collections::VecDeque;
 
-use crate::error::SynacorErr;
+use crate::admin::{AdminCommand, HELP};
+use crate::disasm::Instruction;
+use crate::error::{ErrorKind, RuntimeContext, SynacorErr, ValueSource};
 use crate::opcodes::{OpName, INS_WIDTH};
-use std::{thread, time};
+use crate::prompt::{Prompt, RECENT_LEN};
+use crate::script::{Script, Step};
+use crate::session::{Event, OutputHash, Recorder, Replay};
+use crate::snapshot::Snapshot;
+use std::io::{IsTerminal, Write};
+use std::thread;
 
 const BITS_15: usize = 32768;
+// how much of the recent past is kept for error reports
+const HISTORY_LEN: usize = 16;
+const STACK_TOP: usize = 8;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    Running,
+    Halted,
+    /// Only in headless mode, the VM is stopped at an `in` with no queued input
+    AwaitingInput,
+}
+
+/// What an `in` does once stdin has ended
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum EofPolicy {
+    /// Stop as if the program ran `halt`
+    #[default]
+    Halt,
+    Error,
+    /// Type this line every time the program asks for more
+    Feed(String),
+}
+
+#[derive(Debug, Clone)]
 pub struct VM {
     memory: [u16; BITS_15],
     stack: Vec<u16>,
     registers: [u16; 8],
     addr: usize,
     input: VecDeque<u16>,
-    auto: bool,
-    auto_commands: Vec<String>,
+    /// Lines typed in place of the player, until it runs out
+    script: Option<Script>,
+    recorder: Option<Recorder>,
+    /// Recorded session to play back, taking precedence over the script
+    replay: Option<Replay>,
+    on_eof: EofPolicy,
+    /// Line editor used instead of plain stdin reads
+    prompt: Option<Prompt>,
+    /// Latest output, for the line editor to complete words from
+    recent: String,
+    /// Print every instruction to stderr before running it
+    trace: bool,
+    /// Whether stdin is a terminal, so that the `> ` prompt is worth printing
+    interactive: bool,
+    /// Instructions run so far
+    count: u64,
+    output_hash: OutputHash,
+    // headless VMs buffer their output and wait for `feed` instead of using stdin/stdout
+    headless: bool,
+    output: String,
+    history: VecDeque<usize>,
 }
 
 impl VM {
@@ -30,14 +78,110 @@ impl VM {
             registers: [0; 8],
             addr: 0,
             input: VecDeque::new(),
-            auto,
-            auto_commands: SOLUTION.iter().rev().map(|&s| s.into()).collect(),
+            script: auto.then(Script::solution),
+            recorder: None,
+            replay: None,
+            on_eof: EofPolicy::default(),
+            trace: false,
+            prompt: None,
+            recent: String::new(),
+            interactive: std::io::stdin().is_terminal(),
+            count: 0,
+            output_hash: OutputHash::default(),
+            headless: false,
+            output: String::new(),
+            history: VecDeque::with_capacity(HISTORY_LEN),
         }
     }
 
+    pub fn headless(bytes: Vec<u16>) -> Self {
+        Self {
+            headless: true,
+            ..Self::new(bytes, false)
+        }
+    }
+
+    /// Queue a line of input for a headless VM
+    pub fn feed(&mut self, line: &str) {
+        self.input.extend(line.bytes().map(|x| x as u16));
+    }
+
+    /// Type the lines of a script whenever the program wants input, replacing any other
+    pub fn set_script(&mut self, script: Script) {
+        self.script = Some(script);
+    }
+
+    /// Log every input line and the final halt to a session file
+    pub fn record(&mut self, recorder: Recorder) {
+        self.recorder = Some(recorder);
+    }
+
+    /// Take input from a recorded session, failing as soon as the run diverges from it
+    pub fn replay(&mut self, replay: Replay) {
+        self.replay = Some(replay);
+    }
+
+    /// Read the player's lines through a line editor with history and completion
+    pub fn set_prompt(&mut self, prompt: Prompt) {
+        self.prompt = Some(prompt);
+    }
+
+    pub fn set_eof_policy(&mut self, policy: EofPolicy) {
+        self.on_eof = policy;
+    }
+
+    pub fn instruction_count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn output_hash(&self) -> OutputHash {
+        self.output_hash
+    }
+
+    /// Drain the output buffered by a headless VM
+    pub fn take_output(&mut self) -> String {
+        std::mem::take(&mut self.output)
+    }
+
+    pub fn memory(&self) -> &[u16] {
+        &self.memory
+    }
+
+    pub fn registers(&self) -> &[u16; 8] {
+        &self.registers
+    }
+
+    pub fn stack(&self) -> &[u16] {
+        &self.stack
+    }
+
+    pub fn addr(&self) -> usize {
+        self.addr
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            memory: self.memory.to_vec(),
+            registers: self.registers,
+            stack: self.stack.clone(),
+            addr: self.addr,
+        }
+    }
+
+    /// Resume from a snapshot, any queued input is dropped
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        let len = snapshot.memory.len().min(BITS_15);
+        self.memory = [0; BITS_15];
+        self.memory[..len].copy_from_slice(&snapshot.memory[..len]);
+        self.registers = snapshot.registers;
+        self.stack = snapshot.stack.clone();
+        self.addr = snapshot.addr;
+        self.input.clear();
+    }
+
     // generic so that it can handle the main loop and intermediate errors
-    fn err<T>(&self, details: String) -> Result<T, SynacorErr> {
-        Err(SynacorErr::new_addr(self.addr, details))
+    fn err<T>(&self, kind: ErrorKind) -> Result<T, SynacorErr> {
+        Err(SynacorErr::new_addr(self.addr, kind))
     }
 
     fn read_mem(&self, offset: usize) -> Result<u16, SynacorErr> {
@@ -51,36 +195,62 @@ impl VM {
             } else {
                 Err(SynacorErr::new_addr(
                     self.addr + offset,
-                    format!("Value {} falls outside 15-bit range.", val),
+                    ErrorKind::InvalidValue(*val, ValueSource::Operand),
                 ))
             }
         } else {
-            self.err(format!(
-                "Attempt to access invalid memory address {}",
-                self.addr + offset
-            ))
+            self.err(ErrorKind::MemoryOutOfRange(self.addr + offset))
         }
     }
 
     fn assign_reg(&mut self, new_val: u16) -> Result<(), SynacorErr> {
         match self.memory.get(self.addr + 1) {
             Some(reg) => {
-                if (32768..32775).contains(reg) {
-                    *&mut self.registers[(*reg as usize) - BITS_15] = new_val;
+                if (32768..32776).contains(reg) {
+                    self.registers[(*reg as usize) - BITS_15] = new_val;
                     Ok(())
                 } else {
-                    self.err(format!("Attempted to access invalid register {}", *reg))
+                    self.err(ErrorKind::InvalidRegister(*reg))
                 }
             }
-            None => self.err(format!(
-                "Attempt to access invalid memory address {}",
-                self.addr + 1
-            )),
+            None => self.err(ErrorKind::MemoryOutOfRange(self.addr + 1)),
+        }
+    }
+
+    // text and width of the instruction at an address, or of the value there as data
+    fn disassemble(&self, addr: usize) -> (String, usize) {
+        match Instruction::decode(&self.memory, addr) {
+            Some(ins) => (ins.to_string(), ins.size()),
+            None => match self.memory.get(addr) {
+                Some(val) => (format!("data {:#06x}", val), 1),
+                None => ("outside memory".to_string(), 1),
+            },
+        }
+    }
+
+    fn context(&self) -> RuntimeContext {
+        let (instruction, width) = self.disassemble(self.addr);
+
+        RuntimeContext {
+            addr: self.addr,
+            instruction,
+            width,
+            registers: self.registers,
+            stack_len: self.stack.len(),
+            stack_top: self.stack[self.stack.len().saturating_sub(STACK_TOP)..].to_vec(),
+            history: self.history.iter().copied().collect(),
         }
     }
 
-    // returns true if we should halt
-    fn step(&mut self) -> Result<bool, SynacorErr> {
+    fn step(&mut self) -> Result<State, SynacorErr> {
+        if self.history.len() == HISTORY_LEN {
+            self.history.pop_front();
+        }
+        self.history.push_back(self.addr);
+        if self.trace {
+            eprintln!("{:#06x}: {}", self.addr, self.disassemble(self.addr).0);
+        }
+
         let opcode_id = self.read_mem(0)?;
 
         if let (Some(width), Ok(opname)) = (INS_WIDTH.get(&opcode_id), OpName::try_from(opcode_id))
@@ -92,8 +262,7 @@ impl VM {
             let (ar, br, cr) = (self.read_mem(1), self.read_mem(2), self.read_mem(3));
             let (mut a, mut b, mut c) = (0_u16, 0_u16, 0_u16);
 
-            for (maybe_err, var) in
-                [(ar, &mut a), (br, &mut b), (cr, &mut c)][..*width as usize].iter_mut()
+            for (maybe_err, var) in [(ar, &mut a), (br, &mut b), (cr, &mut c)][..*width].iter_mut()
             {
                 match maybe_err {
                     Err(e) => return Err(e.clone()),
@@ -109,7 +278,7 @@ impl VM {
             let mut admin = false;
 
             match opname {
-                OpName::Halt => (),
+                OpName::Halt => self.halt_event()?,
                 OpName::Set => {
                     self.assign_reg(b)?;
                 }
@@ -120,16 +289,19 @@ impl VM {
                     Some(val) => {
                         self.assign_reg(val)?;
                     }
-                    None => return self.err("Pop called on an empty stack.".to_string()),
+                    None => return self.err(ErrorKind::StackUnderflow(opname)),
+                },
+                OpName::Eq
+                | OpName::Gt
+                | OpName::Add
+                | OpName::Mult
+                | OpName::Mod
+                | OpName::And
+                | OpName::Or
+                | OpName::Not => match opname.eval(b, c) {
+                    Some(res) => self.assign_reg(res)?,
+                    None => return self.err(ErrorKind::DivisionByZero(b)),
                 },
-                OpName::Eq => {
-                    let res = if b == c { 1 } else { 0 };
-                    self.assign_reg(res)?;
-                }
-                OpName::Gt => {
-                    let res = if b > c { 1 } else { 0 };
-                    self.assign_reg(res)?;
-                }
                 OpName::Jmp => {
                     self.addr = a as usize;
                 }
@@ -147,45 +319,18 @@ impl VM {
                         optional_advance = true;
                     }
                 }
-                OpName::Add => {
-                    let res = (b + c) % (BITS_15 as u16);
-                    self.assign_reg(res)?;
-                }
-                OpName::Mult => {
-                    let res = ((b as usize) * (c as usize) % BITS_15) as u16;
-                    self.assign_reg(res)?;
-                }
-                OpName::Mod => {
-                    self.assign_reg(b % c)?;
-                }
-                OpName::And => {
-                    self.assign_reg(b & c)?;
-                }
-                OpName::Or => {
-                    self.assign_reg(b | c)?;
-                }
-                OpName::Not => {
-                    let res = !b % (BITS_15 as u16);
-                    self.assign_reg(res)?;
-                }
                 OpName::Rmem => {
                     if let Some(val) = self.memory.get(b as usize) {
                         self.assign_reg(*val)?;
                     } else {
-                        return self.err(format!(
-                            "Attempt to access invalid memory address {}",
-                            b as usize,
-                        ));
+                        return self.err(ErrorKind::MemoryOutOfRange(b as usize));
                     }
                 }
                 OpName::Wmem => {
                     if let Some(val) = self.memory.get_mut(a as usize) {
                         *val = b;
                     } else {
-                        return self.err(format!(
-                            "Attempt to access invalid memory address {}",
-                            a as usize,
-                        ));
+                        return self.err(ErrorKind::MemoryOutOfRange(a as usize));
                     }
                 }
                 OpName::Call => {
@@ -196,52 +341,69 @@ impl VM {
                     Some(val) => {
                         self.addr = val as usize;
                     }
-                    None => return self.err("Ret called on an empty stack.".to_string()),
+                    None => return self.err(ErrorKind::StackUnderflow(opname)),
                 },
                 OpName::Out => match u8::try_from(a) {
                     Ok(ascii) => {
-                        print!("{}", ascii as char);
+                        self.output_hash.update(ascii);
+                        if self.headless {
+                            self.output.push(ascii as char);
+                        } else {
+                            print!("{}", ascii as char);
+                        }
+                        if self.prompt.is_some() && ascii.is_ascii() {
+                            self.recent.push(ascii as char);
+                            if self.recent.len() > 2 * RECENT_LEN {
+                                self.recent.drain(..RECENT_LEN);
+                            }
+                        }
                     }
                     Err(_) => {
-                        return self.err(format!("Invalid ASCII code {}", a));
+                        return self.err(ErrorKind::InvalidAscii(a));
                     }
                 },
                 OpName::In => {
                     if self.input.is_empty() {
-                        let mut line = String::new();
-                        print!("\n> ");
-
-                        if self.auto {
-                            if let Some(command) = self.auto_commands.pop() {
-                                line = command;
-                                let sleep = time::Duration::from_millis(200);
-
-                                for c in line.chars() {
-                                    print!("{}", c);
-                                    std::io::Write::flush(&mut std::io::stdout())?;
-                                    thread::sleep(sleep);
-                                }
-                            } else {
-                                self.auto = false;
-                                std::io::Write::flush(&mut std::io::stdout())?;
-                                let _ = std::io::stdin().read_line(&mut line)?;
-                            }
-                        } else {
-                            std::io::Write::flush(&mut std::io::stdout())?;
-                            let _ = std::io::stdin().read_line(&mut line)?;
+                        let line = match self.replay_line()? {
+                            Some(line) => line,
+                            None => match self.script_line()? {
+                                Some(line) => line,
+                                None if self.headless => return Ok(State::AwaitingInput),
+                                None => match self.read_player()? {
+                                    line if !line.is_empty() => line,
+                                    _ => match &self.on_eof {
+                                        EofPolicy::Halt => {
+                                            self.halt_event()?;
+                                            return Ok(State::Halted);
+                                        }
+                                        EofPolicy::Error => return self.err(ErrorKind::EndOfInput),
+                                        EofPolicy::Feed(line) => format!("{}\n", line),
+                                    },
+                                },
+                            },
+                        };
+                        if let Some(recorder) = &self.recorder {
+                            recorder.record(&Event::Input {
+                                count: self.count,
+                                hash: self.output_hash,
+                                line: line.clone(),
+                            })?;
                         }
 
-                        if line == "admin\n" {
-                            admin = true;
-                            println!("Address: {}", self.addr);
-                            println!("Registers: {:?}", self.registers);
-                        } else {
-                            self.input = line.bytes().map(|x| x as u16).collect();
+                        match AdminCommand::parse(&line) {
+                            Some(command) => {
+                                admin = true;
+                                self.admin(command);
+                            }
+                            None => self.input = line.bytes().map(|x| x as u16).collect(),
                         }
                     }
 
                     if !admin {
-                        let c = self.input.pop_front().unwrap();
+                        // only an empty replayed line leaves nothing to read
+                        let Some(c) = self.input.pop_front() else {
+                            return self.err(ErrorKind::EndOfInput);
+                        };
                         self.assign_reg(c)?;
                     }
                 }
@@ -251,78 +413,254 @@ impl VM {
             if (opname.advance() || optional_advance) && !admin {
                 self.addr += width + 1;
             };
+            self.count += 1;
 
             match opname {
-                OpName::Halt => Ok(true),
-                _ => Ok(false),
+                OpName::Halt => Ok(State::Halted),
+                _ => Ok(State::Running),
             }
         } else {
-            return self.err(format!("Opcode {} is not valid.", opcode_id));
+            self.err(ErrorKind::InvalidOpcode(opcode_id))
+        }
+    }
+
+    // admin output goes where program output would, without counting towards its hash
+    fn admin_print(&mut self, text: &str) {
+        if self.headless {
+            self.output.push_str(text);
+            self.output.push('\n');
+        } else {
+            println!("{}", text);
         }
     }
 
-    pub fn run(&mut self) -> Result<bool, SynacorErr> {
+    fn admin(&mut self, command: Result<AdminCommand, String>) {
+        let command = match command {
+            Ok(command) => command,
+            Err(message) => return self.admin_print(&message),
+        };
+
+        let text = match command {
+            AdminCommand::Status => {
+                format!("Address: {}\nRegisters: {:?}", self.addr, self.registers)
+            }
+            AdminCommand::Set(reg, val) => {
+                self.registers[reg] = val;
+                format!("${} = {:#06x}", reg, val)
+            }
+            AdminCommand::Poke(addr, val) => {
+                self.memory[addr] = val;
+                format!("{:#06x}: {:#06x}", addr, val)
+            }
+            AdminCommand::Peek(addr, len) => {
+                let end = (addr + len).min(BITS_15);
+                (addr..end)
+                    .step_by(8)
+                    .map(|start| {
+                        let values = self.memory[start..(start + 8).min(end)]
+                            .iter()
+                            .map(|val| format!("{:#06x}", val))
+                            .collect::<Vec<String>>()
+                            .join(" ");
+                        format!("{:#06x}: {}", start, values)
+                    })
+                    .collect::<Vec<String>>()
+                    .join("\n")
+            }
+            AdminCommand::Stack if self.stack.is_empty() => "Stack: empty".to_string(),
+            AdminCommand::Stack => format!(
+                "Stack: {} ({} values, top last)",
+                self.stack
+                    .iter()
+                    .map(|val| format!("{:#06x}", val))
+                    .collect::<Vec<String>>()
+                    .join(" "),
+                self.stack.len()
+            ),
+            AdminCommand::Dis(addr, count) => {
+                let mut lines = Vec::new();
+                let mut at = addr;
+                while lines.len() < count && at < BITS_15 {
+                    let (text, width) = self.disassemble(at);
+                    lines.push(format!("{:#06x}: {}", at, text));
+                    at += width;
+                }
+                lines.join("\n")
+            }
+            AdminCommand::Save(path) => match self.snapshot().save(&path) {
+                Ok(()) => format!("Saved a snapshot to {}", path.display()),
+                Err(e) => e.to_string(),
+            },
+            AdminCommand::Load(path) => match Snapshot::load(&path) {
+                Ok(snapshot) => {
+                    self.restore(&snapshot);
+                    format!("Loaded the snapshot from {}", path.display())
+                }
+                Err(e) => e.to_string(),
+            },
+            AdminCommand::Trace(on) => {
+                self.trace = on;
+                format!("Tracing is {}", if on { "on" } else { "off" })
+            }
+            AdminCommand::Help => HELP.to_string(),
+        };
+
+        self.admin_print(&text);
+    }
+
+    /// Next recorded line, once the run has caught up with the point it was typed at
+    fn replay_line(&mut self) -> Result<Option<String>, SynacorErr> {
+        let Some(replay) = self.replay.as_mut() else {
+            return Ok(None);
+        };
+        let expected = replay.events.pop_front();
+
+        match expected {
+            Some(Event::Input { count, hash, line })
+                if count == self.count && hash == self.output_hash =>
+            {
+                if !self.headless {
+                    print!("{}{}", self.prompt_text(), line);
+                }
+                Ok(Some(line))
+            }
+            Some(expected) => self.diverged(&expected, "asked for input"),
+            // the recording stopped without a halt, so the player takes over
+            None => {
+                self.replay = None;
+                Ok(None)
+            }
+        }
+    }
+
+    // a run ending, by `halt` or by the input running out
+    fn halt_event(&mut self) -> Result<(), SynacorErr> {
+        self.session_event(Event::Halt {
+            count: self.count,
+            hash: self.output_hash,
+        })
+    }
+
+    fn session_event(&mut self, event: Event) -> Result<(), SynacorErr> {
+        if let Some(recorder) = &self.recorder {
+            recorder.record(&event)?;
+        }
+        let expected = self.replay.as_mut().and_then(|r| r.events.pop_front());
+        match expected {
+            Some(expected) if expected != event => self.diverged(&expected, "halted"),
+            _ => Ok(()),
+        }
+    }
+
+    fn diverged<T>(&self, expected: &Event, what: &str) -> Result<T, SynacorErr> {
+        self.err(ErrorKind::Replay(format!(
+            "The run diverged from the recording, which expected `{}`, when the program {} after {} instructions with output hash {:#018x}.",
+            expected, what, self.count, self.output_hash.0
+        )))
+    }
+
+    /// Next line of the script, after carrying out the directives before it
+    fn script_line(&mut self) -> Result<Option<String>, SynacorErr> {
+        while let Some(step) = self.script.as_mut().and_then(|s| s.steps.pop_front()) {
+            match step {
+                Step::Line(line) => {
+                    if !self.headless {
+                        self.type_line(&line)?;
+                    }
+                    return Ok(Some(line + "\n"));
+                }
+                Step::Delay(delay) => {
+                    if let Some(script) = self.script.as_mut() {
+                        script.delay = delay;
+                    }
+                }
+                Step::Pause if self.headless => return Ok(None),
+                Step::Pause => {
+                    // the player types until an empty line hands control back
+                    let line = self.read_player()?;
+                    if !line.trim().is_empty() {
+                        if let Some(script) = self.script.as_mut() {
+                            script.steps.push_front(Step::Pause);
+                        }
+                        return Ok(Some(line));
+                    }
+                }
+                Step::SetRegister(reg, val) => self.registers[reg] = val,
+                Step::Save(path) => self.snapshot().save(&path)?,
+            }
+        }
+        Ok(None)
+    }
+
+    fn prompt_text(&self) -> &'static str {
+        if self.interactive {
+            "\n> "
+        } else {
+            ""
+        }
+    }
+
+    // a line from the player, empty once stdin has ended
+    fn read_player(&mut self) -> Result<String, SynacorErr> {
+        match &self.prompt {
+            Some(prompt) if self.interactive => {
+                println!();
+                prompt.read_line("> ", &self.recent)
+            }
+            _ => {
+                print!("{}", self.prompt_text());
+                std::io::stdout().flush()?;
+                read_line()
+            }
+        }
+    }
+
+    fn type_line(&self, line: &str) -> Result<(), SynacorErr> {
+        print!("{}", self.prompt_text());
+        let delay = self.script.as_ref().map(|s| s.delay).unwrap_or_default();
+        if delay.is_zero() {
+            println!("{}", line);
+            return Ok(());
+        }
+
+        for c in line.chars() {
+            print!("{}", c);
+            std::io::stdout().flush()?;
+            thread::sleep(delay);
+        }
+        println!();
+        Ok(())
+    }
+
+    /// Run until the program halts, or a headless VM needs more input
+    pub fn run(&mut self) -> Result<State, SynacorErr> {
         loop {
-            let step = self.step();
+            let step = self.step().map_err(|e| e.with_context(self.context()));
+            match step {
+                Err(_) | Ok(State::Halted) | Ok(State::AwaitingInput) => return step,
+                Ok(State::Running) => (),
+            }
+        }
+    }
+
+    /// Run like `run`, but fail once `max_steps` instructions go by without either
+    pub fn run_for(&mut self, max_steps: usize) -> Result<State, SynacorErr> {
+        for _ in 0..max_steps {
+            let step = self.step().map_err(|e| e.with_context(self.context()));
             match step {
-                Err(_) | Ok(true) => return step,
-                Ok(false) => (),
+                Err(_) | Ok(State::Halted) | Ok(State::AwaitingInput) => return step,
+                Ok(State::Running) => (),
             }
         }
+        Err(
+            SynacorErr::new_addr(self.addr, ErrorKind::StepLimit(max_steps))
+                .with_context(self.context()),
+        )
     }
 }
 
-const SOLUTION: &'static [&'static str] = &[
-    "take tablet\n",
-    "use tablet\n",
-    "doorway\n",
-    "north\n",
-    "north\n",
-    "bridge\n",
-    "continue\n",
-    "down\n",
-    "east\n",
-    "take empty lantern\n",
-    "west\n",
-    "west\n",
-    "passage\n",
-    "ladder\n",
-    "west\n",
-    "north\n",
-    "south\n",
-    "north\n",
-    "take can\n",
-    "use can\n",
-    "use lantern\n",
-    "west\n",
-    "ladder\n",
-    "darkness\n",
-    "continue\n",
-    "west\n",
-    "west\n",
-    "west\n",
-    "west\n",
-    "north\n",
-    "take red coin\n",
-    "north\n",
-    "east\n",
-    "take concave coin\n",
-    "down\n",
-    "take corroded coin\n",
-    "up\n",
-    "west\n",
-    "west\n",
-    "take blue coin\n",
-    "up\n",
-    "take shiny coin\n",
-    "down\n",
-    "east\n",
-    "use blue coin\n",
-    "use red coin\n",
-    "use shiny coin\n",
-    "use concave coin\n",
-    "use corroded coin\n",
-    "north\n",
-    "take teleporter\n",
-    "use teleporter\n",
-];
+fn read_line() -> Result<String, SynacorErr> {
+    let mut line = String::new();
+    std::io::stdin().read_line(&mut line)?;
+    Ok(line)
+}
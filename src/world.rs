@@ -0,0 +1,262 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write;
+use std::path::Path;
+
+use serde_json::{json, Value};
+
+use crate::error::SynacorErr;
+use crate::snapshot::Snapshot;
+use crate::vm::{State, VM};
+
+// Rooms are printed as a title, a description, then optional lists of items and exits:
+//
+// == Foothills ==
+// You find yourself standing at the base of an enormous mountain.
+//
+// Things of interest here:
+// - tablet
+//
+// There are 2 exits:
+// - doorway
+// - south
+//
+// Every exit of every room is tried from a snapshot taken on arrival, breadth-first. Rooms
+// are told apart by their text, which merges rooms that read the same, unless the address
+// of the game's current room is known.
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Room {
+    pub title: String,
+    pub description: String,
+    pub items: Vec<String>,
+    pub exits: Vec<String>,
+}
+
+/// Where an exit leads
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dest {
+    Room(usize),
+    /// The game ended or printed something other than a room
+    End,
+    /// A new room, left unexplored once `max_rooms` were mapped
+    Unexplored,
+}
+
+/// An exit tried from a room
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Exit {
+    pub from: usize,
+    pub name: String,
+    pub to: Dest,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct World {
+    pub rooms: Vec<Room>,
+    pub exits: Vec<Exit>,
+}
+
+/// The last room described in some game output
+pub fn parse_room(output: &str) -> Option<Room> {
+    let start = output.rfind("\n== ").map_or(output.find("== ")?, |i| i + 1);
+    let mut lines = output[start..].lines();
+
+    let title = lines.next()?.strip_prefix("== ")?.strip_suffix(" ==")?;
+    let mut room = Room {
+        title: title.to_string(),
+        description: String::new(),
+        items: Vec::new(),
+        exits: Vec::new(),
+    };
+
+    let mut list = None;
+    for line in lines {
+        match (line.strip_prefix("- "), line) {
+            (Some(entry), _) => match list {
+                Some(true) => room.exits.push(entry.to_string()),
+                Some(false) => room.items.push(entry.to_string()),
+                None => (),
+            },
+            (None, "Things of interest here:") => list = Some(false),
+            (None, line)
+                if line.starts_with("There is 1 exit") || line.starts_with("There are ") =>
+            {
+                list = Some(true)
+            }
+            (None, "What do you do?") => break,
+            (None, "") => list = None,
+            (None, line) if room.description.is_empty() => room.description = line.to_string(),
+            (None, line) => {
+                room.description.push(' ');
+                room.description.push_str(line);
+            }
+        }
+    }
+
+    Some(room)
+}
+
+/// Map every room reachable by walking from wherever the program first asks for input,
+/// stopping after `max_rooms`. With `room_addr`, rooms are told apart by the value there.
+pub fn explore(
+    memory: Vec<u16>,
+    room_addr: Option<usize>,
+    max_rooms: usize,
+) -> Result<World, SynacorErr> {
+    let mut vm = VM::headless(memory);
+    let mut world = World::default();
+    if vm.run()? != State::AwaitingInput {
+        return Ok(world);
+    }
+
+    let mut ids: HashMap<(Option<u16>, Room), usize> = HashMap::new();
+    let mut todo: VecDeque<(usize, Snapshot)> = VecDeque::new();
+
+    // the room the VM is in now, added and queued if it is new
+    let mut arrive = |vm: &mut VM, world: &mut World, todo: &mut VecDeque<_>| {
+        let Some(room) = parse_room(&vm.take_output()) else {
+            return Dest::End;
+        };
+        let key = (room_addr.map(|addr| vm.memory()[addr]), room);
+        if let Some(id) = ids.get(&key) {
+            return Dest::Room(*id);
+        }
+        if world.rooms.len() == max_rooms {
+            return Dest::Unexplored;
+        }
+
+        let id = world.rooms.len();
+        world.rooms.push(key.1.clone());
+        ids.insert(key, id);
+        todo.push_back((id, vm.snapshot()));
+        Dest::Room(id)
+    };
+
+    arrive(&mut vm, &mut world, &mut todo);
+
+    while let Some((from, snapshot)) = todo.pop_front() {
+        for name in world.rooms[from].exits.clone() {
+            vm.restore(&snapshot);
+            vm.take_output();
+            vm.feed(&format!("{}\n", name));
+
+            let to = match vm.run()? {
+                State::AwaitingInput => arrive(&mut vm, &mut world, &mut todo),
+                _ => Dest::End,
+            };
+            world.exits.push(Exit { from, name, to });
+        }
+    }
+
+    Ok(world)
+}
+
+// room ids, with `null` for the end of the game and "unexplored" past the room limit
+fn dest_json(dest: Dest) -> Value {
+    match dest {
+        Dest::Room(id) => json!(id),
+        Dest::End => Value::Null,
+        Dest::Unexplored => json!("unexplored"),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl World {
+    pub fn to_json(&self) -> Value {
+        let rooms: Vec<Value> = self
+            .rooms
+            .iter()
+            .enumerate()
+            .map(|(id, room)| {
+                let exits: serde_json::Map<String, Value> = self
+                    .exits
+                    .iter()
+                    .filter(|exit| exit.from == id)
+                    .map(|exit| (exit.name.clone(), dest_json(exit.to)))
+                    .collect();
+                json!({
+                    "id": id,
+                    "title": room.title,
+                    "description": room.description,
+                    "items": room.items,
+                    "exits": exits,
+                })
+            })
+            .collect();
+        json!({ "rooms": rooms })
+    }
+
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph world {\n");
+        dot.push_str("  node [shape=box];\n");
+
+        for (id, room) in self.rooms.iter().enumerate() {
+            let items: String = room
+                .items
+                .iter()
+                .map(|item| format!("\\n+ {}", escape(item)))
+                .collect();
+            let _ = writeln!(
+                dot,
+                "  room_{} [label=\"{}{}\"];",
+                id,
+                escape(&room.title),
+                items
+            );
+        }
+        if self.exits.iter().any(|exit| exit.to == Dest::End) {
+            dot.push_str("  end [label=\"game over\", shape=plaintext];\n");
+        }
+        if self.exits.iter().any(|exit| exit.to == Dest::Unexplored) {
+            dot.push_str("  unexplored [label=\"not explored\", shape=plaintext];\n");
+        }
+
+        for exit in self.exits.iter() {
+            let (to, style) = match exit.to {
+                Dest::Room(to) => (format!("room_{}", to), ""),
+                Dest::End => ("end".to_string(), ""),
+                Dest::Unexplored => ("unexplored".to_string(), ", style=dashed"),
+            };
+            let _ = writeln!(
+                dot,
+                "  room_{} -> {} [label=\"{}\"{}];",
+                exit.from,
+                to,
+                escape(&exit.name),
+                style
+            );
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Explore the program and write the map as JSON, DOT or both
+pub fn write_world(
+    memory: Vec<u16>,
+    room_addr: Option<usize>,
+    max_rooms: usize,
+    json_path: Option<&Path>,
+    dot_path: Option<&Path>,
+) -> Result<(), SynacorErr> {
+    let world = explore(memory, room_addr, max_rooms)?;
+    println!(
+        "Mapped {} rooms and {} exits",
+        world.rooms.len(),
+        world.exits.len()
+    );
+
+    if let Some(path) = json_path {
+        std::fs::write(path, format!("{:#}\n", world.to_json()))?;
+        println!("Created JSON file {}", path.display());
+    }
+    if let Some(path) = dot_path {
+        std::fs::write(path, world.to_dot())?;
+        println!("Created DOT file {}", path.display());
+    }
+    Ok(())
+}
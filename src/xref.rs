@@ -0,0 +1,85 @@
+use std::collections::BTreeMap;
+
+use itertools::Itertools;
+use strum_macros::Display;
+
+use crate::disasm::Instruction;
+use crate::opcodes::OpName;
+
+#[derive(Display, Debug, Clone, Copy, PartialEq, Eq)]
+#[strum(serialize_all = "lowercase")]
+pub enum RefKind {
+    Jump,
+    Call,
+    Read,
+    Write,
+}
+
+/// An instruction at `from` that refers to some address
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reference {
+    pub from: usize,
+    pub kind: RefKind,
+}
+
+/// For every address, the instructions that jump to, call, read or write it
+#[derive(Debug, Clone, Default)]
+pub struct Xrefs {
+    pub refs: BTreeMap<usize, Vec<Reference>>,
+}
+
+impl Xrefs {
+    // uses the same linear sweep as `u16_to_asm`, so references line up with the listing
+    pub fn new(memory: &[u16]) -> Self {
+        let mut xrefs = Self::default();
+        let mut addr = 0;
+
+        while addr < memory.len() {
+            match Instruction::decode(memory, addr) {
+                Some(ins) => {
+                    xrefs.record(&ins);
+                    addr = ins.next_addr();
+                }
+                None => addr += 1,
+            }
+        }
+
+        xrefs
+    }
+
+    fn record(&mut self, ins: &Instruction) {
+        let (operand, kind) = match ins.opname {
+            OpName::Jmp | OpName::Jt | OpName::Jf => (ins.target_operand(), RefKind::Jump),
+            OpName::Call => (ins.target_operand(), RefKind::Call),
+            OpName::Rmem => (Some(ins.operands[1]), RefKind::Read),
+            OpName::Wmem => (Some(ins.operands[0]), RefKind::Write),
+            _ => return,
+        };
+
+        if let Some(to) = operand.and_then(|operand| operand.literal()) {
+            self.refs.entry(to as usize).or_default().push(Reference {
+                from: ins.addr,
+                kind,
+            });
+        }
+    }
+
+    pub fn to(&self, addr: usize) -> &[Reference] {
+        self.refs.get(&addr).map_or(&[], |refs| refs.as_slice())
+    }
+
+    /// Assembly comment listing the references to `addr`, if there are any
+    pub fn comment(&self, addr: usize) -> Option<String> {
+        let refs = self.to(addr);
+        if refs.is_empty() {
+            None
+        } else {
+            Some(format!(
+                "; xref {}",
+                refs.iter()
+                    .map(|r| format!("{} {:#06x}", r.kind, r.from))
+                    .join(", ")
+            ))
+        }
+    }
+}
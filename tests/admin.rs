@@ -0,0 +1,60 @@
+mod common;
+
+#[cfg(test)]
+mod test {
+    use crate::common::ECHO;
+    use std::path::{Path, PathBuf};
+    use synacor::admin::AdminCommand;
+    use synacor::convert::assemble_lines;
+    use synacor::error::SynacorErr;
+    use synacor::script::Script;
+    use synacor::vm::{State, VM};
+
+    #[test]
+    fn parse() {
+        let parse = |line| AdminCommand::parse(line).map(|command| command.ok());
+
+        assert_eq!(parse("north\n"), None);
+        assert_eq!(parse("admin\n"), Some(Some(AdminCommand::Status)));
+        assert_eq!(
+            parse("admin set $7 0x7fff\n"),
+            Some(Some(AdminCommand::Set(7, 0x7fff)))
+        );
+        assert_eq!(
+            parse("admin peek 0x10 32"),
+            Some(Some(AdminCommand::Peek(0x10, 32)))
+        );
+        assert_eq!(
+            parse("admin load start.snap"),
+            Some(Some(AdminCommand::Load(PathBuf::from("start.snap"))))
+        );
+        assert_eq!(parse("admin set $8 1"), Some(None));
+        assert_eq!(parse("admin poke 0 0x8008"), Some(None));
+    }
+
+    #[test]
+    fn commands_keep_game_input() -> Result<(), SynacorErr> {
+        let memory = assemble_lines(ECHO, Path::new("<asm>"))?.concat();
+        let script = "a\nadmin set $7 0x0005\nadmin poke 0x000a 0x002a\nadmin peek 0x0009 2\nadmin stack\nadmin dis 0x0009 2\nadmin bogus\nb\nq\n";
+
+        let mut vm = VM::headless(memory);
+        vm.set_script(Script::parse(script, Path::new("moves.txt"))?);
+        assert_eq!(vm.run()?, State::Halted);
+
+        // the first line is echoed, then `out $0` is patched to print `*` instead
+        assert_eq!(
+            vm.take_output(),
+            "a
+$7 = 0x0005
+0x000a: 0x002a
+0x0009: 0x0013 0x002a
+Stack: empty
+0x0009: out 0x002a
+0x000b: jmp 0x0000
+Unknown admin command `bogus`, try `admin help`.
+**"
+        );
+        assert_eq!(vm.registers()[7], 5);
+        Ok(())
+    }
+}
@@ -0,0 +1,39 @@
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+    use synacor::bf::bf2syn;
+    use synacor::convert::assemble_lines;
+    use synacor::error::{ErrorKind, SynacorErr};
+    use synacor::vm::{State, VM};
+
+    fn vm(code: &str) -> Result<VM, SynacorErr> {
+        let asm = bf2syn(code, Path::new("<bf>"))?;
+        Ok(VM::headless(
+            assemble_lines(&asm, Path::new("<asm>"))?.concat(),
+        ))
+    }
+
+    #[test]
+    fn hello_world() -> Result<(), SynacorErr> {
+        // the Wikipedia hello world, which wraps cells below zero
+        let code = "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.";
+        let mut vm = vm(code)?;
+
+        assert_eq!(vm.run()?, State::Halted);
+        assert_eq!(vm.take_output(), "Hello World!\n");
+        Ok(())
+    }
+
+    #[test]
+    fn cat() -> Result<(), SynacorErr> {
+        let mut vm = vm("copy input to output: ,[.,]")?;
+        vm.feed("synacor\n");
+
+        assert_eq!(vm.run()?, State::AwaitingInput);
+        assert_eq!(vm.take_output(), "synacor\n");
+
+        let err = bf2syn("[[]", Path::new("<bf>")).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::Parse { .. }));
+        Ok(())
+    }
+}
@@ -0,0 +1,53 @@
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+    use synacor::cfg::{Cfg, Edge, EdgeKind};
+    use synacor::convert::bin_to_u16;
+    use synacor::error::SynacorErr;
+
+    #[test]
+    fn small_program() {
+        // 0x0000: jt $0 0x0005
+        // 0x0003: call 0x0009
+        // 0x0005: out 0x0041
+        // 0x0007: halt
+        // 0x0009: ret
+        let memory = vec![7, 32768, 5, 17, 9, 19, 65, 0, 0, 18];
+        let cfg = Cfg::new(&memory);
+
+        assert_eq!(cfg.functions.keys().collect::<Vec<_>>(), vec![&0, &9]);
+
+        let main = &cfg.functions[&0];
+        assert_eq!(main.blocks.keys().collect::<Vec<_>>(), vec![&0, &3, &5]);
+        assert_eq!(
+            main.blocks[&0].successors,
+            vec![
+                Edge {
+                    target: 5,
+                    kind: EdgeKind::Taken
+                },
+                Edge {
+                    target: 3,
+                    kind: EdgeKind::NotTaken
+                }
+            ]
+        );
+        assert_eq!(main.blocks[&3].calls, vec![9]);
+
+        let dot = cfg.to_dot();
+        assert!(dot.contains("0x0005: out 0x0041\\l0x0007: halt\\l"));
+        assert!(dot.contains("\"0x0000_0x0003\" -> \"0x0009_0x0009\" [style=dashed];"));
+    }
+
+    #[test]
+    fn challenge_binary() -> Result<(), SynacorErr> {
+        let memory = bin_to_u16(&PathBuf::from("examples/challenge.bin"))?;
+        let cfg = Cfg::new(&memory);
+
+        // reached only through `set $0 0x051d; call $0` in the self-test
+        assert!(cfg.functions.contains_key(&0x051d));
+        assert!(cfg.functions.contains_key(&0x06d1));
+
+        Ok(())
+    }
+}
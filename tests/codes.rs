@@ -0,0 +1,42 @@
+#[cfg(test)]
+mod test {
+    use synacor::codes::{find_codes, harvest, Code};
+    use synacor::convert::bin_to_u16;
+    use synacor::error::SynacorErr;
+    use synacor::script::Script;
+
+    fn code(stage: &str, code: &str) -> Code {
+        Code {
+            stage: stage.to_string(),
+            code: code.to_string(),
+        }
+    }
+
+    #[test]
+    fn finds_codes_by_phrase() {
+        let output = "You find yourself writing \"pWDWTEfURAdS\" on the tablet.\n\nSomeone seems to have drawn a message in the sand here:\n\n    tooShort\n\nThrough the mirror, you see \"bpqdAAAAAAAA\" scrawled in charcoal on your forehead.\n";
+
+        assert_eq!(
+            find_codes(output),
+            vec![
+                code("tablet", "pWDWTEfURAdS"),
+                code("mirror", "AAAAAAAAbpqd"),
+            ]
+        );
+    }
+
+    #[test]
+    fn harvests_walkthrough() -> Result<(), SynacorErr> {
+        let memory = bin_to_u16(&"examples/challenge.bin".into())?;
+        let stages: Vec<String> = harvest(memory, Script::solution())?
+            .into_iter()
+            .map(|code| code.stage)
+            .collect();
+
+        assert_eq!(
+            stages,
+            vec!["website", "self-test", "tablet", "maze", "teleporter"]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,2 @@
+// echo input until a `q`
+pub const ECHO: &str = "in $0\neq $1 $0 0x0071\njt $1 0x000d\nout $0\njmp 0x0000\nhalt\n";
@@ -0,0 +1,107 @@
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+    use synacor::compile::compile;
+    use synacor::convert::assemble_lines;
+    use synacor::error::{ErrorKind, Location, SynacorErr};
+    use synacor::vm::VM;
+
+    fn output(code: &str, input: &str) -> Result<String, SynacorErr> {
+        let asm = compile(code, Path::new("<src>"))?;
+        let mut vm = VM::headless(assemble_lines(&asm, Path::new("<asm>"))?.concat());
+        vm.feed(input);
+        vm.run()?;
+        Ok(vm.take_output())
+    }
+
+    #[test]
+    fn recursion_and_loops() -> Result<(), SynacorErr> {
+        let code = "
+let limit = 6;
+
+fn fib(n) {
+    if (n < 2) { return n; }
+    return fib(n - 1) + fib(n - 2);
+}
+
+fn main() {
+    let i = 0;
+    while (1) {
+        i = i + 1;
+        if (i % 2 == 0) { continue; } else if (i > limit) { break; }
+        print(i, \":\", fib(i), \" \");
+    }
+    print(1000 / 7, \" \", -1, \" \", !(3 <= 2 || 0), \"\\n\");
+}
+";
+        assert_eq!(output(code, "")?, "1:1 3:2 5:5 142 32767 1\n");
+        Ok(())
+    }
+
+    #[test]
+    fn builtins() -> Result<(), SynacorErr> {
+        // echo a line backwards, using memory past the data stack as a buffer
+        let code = "
+fn main() {
+    let len = 0;
+    let c = getc();
+    while (c != '\\n') {
+        poke(0x7000 + len, c);
+        len = len + 1;
+        c = getc();
+    }
+    while (len) {
+        len = len - 1;
+        putc(peek(0x7000 + len));
+    }
+    halt();
+    print(\"unreachable\");
+}
+";
+        assert_eq!(output(code, "abc\n")?, "cba");
+        Ok(())
+    }
+
+    #[test]
+    fn errors() {
+        let kind = |code| compile(code, Path::new("<src>")).unwrap_err().kind;
+
+        assert!(matches!(kind("fn f() {}"), ErrorKind::Compile(_)));
+        assert!(matches!(
+            kind("fn main() { x = 1; }"),
+            ErrorKind::Compile(_)
+        ));
+        assert!(matches!(
+            kind("fn main() { main(1); }"),
+            ErrorKind::Compile(_)
+        ));
+        assert!(matches!(
+            kind("fn main() { break; }"),
+            ErrorKind::Compile(_)
+        ));
+        assert!(matches!(
+            kind("fn main() { 1 +; }"),
+            ErrorKind::Parse { .. }
+        ));
+        assert!(matches!(
+            kind("fn main() { 40000; }"),
+            ErrorKind::InvalidValue(..)
+        ));
+
+        // only ASCII can be printed, reported at the string
+        let err = compile("fn main() { print(\"café\"); }", Path::new("<src>")).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::Compile(_)));
+        assert!(matches!(
+            err.location,
+            Location::Code {
+                start: 18,
+                end: 25,
+                ..
+            }
+        ));
+
+        // `%` and `/` by zero fault at run time
+        let err = output("fn main() { let z; print(5 / z); }", "").unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::DivisionByZero(5)));
+    }
+}
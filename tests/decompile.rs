@@ -0,0 +1,63 @@
+#[cfg(test)]
+mod test {
+    use synacor::decompile::decompile;
+
+    #[test]
+    fn countdown_loop() {
+        // 0x0000: set $0 0x0003
+        // 0x0003: jf $0 0x000c
+        // 0x0006: add $0 $0 0x7fff
+        // 0x000a: jmp 0x0003
+        // 0x000c: out 0x0041
+        // 0x000e: halt
+        let memory = vec![
+            1, 32768, 3, 8, 32768, 12, 9, 32768, 32768, 32767, 6, 3, 19, 65, 0,
+        ];
+
+        let expected = "\
+fn_0x0000() {
+    r0 = 0x0003;
+    while (r0) {
+        r0 = r0 + 0x7fff;
+    }
+    print(\"A\");
+    halt();
+}
+";
+        assert_eq!(decompile(&memory, None), expected);
+    }
+
+    #[test]
+    fn callee_saved_registers() {
+        // 0x0000: set $0 0x0002
+        // 0x0003: call 0x0006
+        // 0x0005: halt
+        // 0x0006: push $1
+        // 0x0008: add $1 $0 $0
+        // 0x000c: out $1
+        // 0x000e: pop $1
+        // 0x0010: ret
+        let memory = vec![
+            1, 32768, 2, 17, 6, 0, 2, 32769, 9, 32769, 32768, 32768, 19, 32769, 3, 32769, 18,
+        ];
+
+        let expected = "\
+// saves r1
+fn_0x0006(r0) {
+    r1 = r0 + r0;
+    out(r1);
+    return;
+}
+";
+        assert_eq!(decompile(&memory, Some(6)), expected);
+
+        let caller = "\
+fn_0x0000() {
+    r0 = 0x0002;
+    fn_0x0006(r0);
+    halt();
+}
+";
+        assert!(decompile(&memory, None).starts_with(caller));
+    }
+}
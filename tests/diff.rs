@@ -0,0 +1,55 @@
+#[cfg(test)]
+mod test {
+    use synacor::diff::{diff, Image};
+    use synacor::error::SynacorErr;
+    use synacor::snapshot::Snapshot;
+    use synacor::vm::{State, VM};
+
+    #[test]
+    fn programs() {
+        // 0x0000: out 0x0041
+        // 0x0002: halt
+        // 0x0003: data
+        let a = Image::Program(vec![19, 65, 0, 7]);
+        let b = Image::Program(vec![21, 21, 0, 8]);
+
+        let expected = "\
+@@ 0x0000..0x0002 code @@
+- 0x0000: out 0x0041
++ 0x0000: noop
++ 0x0001: noop
+@@ 0x0003..0x0004 data, 1 values changed @@
+- 0x0007
++ 0x0008
+";
+        assert_eq!(diff(&a, &b), expected);
+        assert_eq!(diff(&a, &a), "");
+    }
+
+    #[test]
+    fn snapshots() -> Result<(), SynacorErr> {
+        // 0x0000: set $2 0x0007
+        // 0x0003: push $2
+        // 0x0005: in $0
+        // 0x0007: halt
+        let mut vm = VM::headless(vec![1, 32770, 7, 2, 32770, 20, 32768, 0]);
+        let before = vm.snapshot();
+        assert_eq!(vm.run()?, State::AwaitingInput);
+        let after = vm.snapshot();
+
+        // snapshots survive a trip through their file format
+        assert_eq!(Snapshot::from_bytes(&after.to_bytes())?, after);
+
+        let expected = "\
+address: 0x0000 -> 0x0005
+$2: 0x0000 -> 0x0007
+stack[0..]: [] -> [0x0007]
+";
+        assert_eq!(
+            diff(&Image::Snapshot(before), &Image::Snapshot(after)),
+            expected
+        );
+
+        Ok(())
+    }
+}
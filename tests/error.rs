@@ -0,0 +1,140 @@
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+    use std::path::PathBuf;
+    use synacor::cli::exit_code;
+    use synacor::convert::{asm_to_u16, asm_to_u16_with_map};
+    use synacor::error::{ErrorKind, Location, SynacorErr, ValueSource};
+    use synacor::opcodes::OpName;
+    use synacor::vm::VM;
+
+    #[test]
+    fn runtime_kinds() {
+        // pop with an empty stack
+        let err = VM::headless(vec![3, 32768]).run().unwrap_err();
+        assert_eq!(err.kind, ErrorKind::StackUnderflow(OpName::Pop));
+        assert_eq!(
+            err.to_string(),
+            "Failure at address 0.\n\nPop called on an empty stack."
+        );
+
+        // opcode 22 does not exist
+        let err = VM::headless(vec![22]).run().unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidOpcode(22));
+
+        // out 0x0100
+        let err = VM::headless(vec![19, 256]).run().unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidAscii(256));
+
+        // out 32776
+        let err = VM::headless(vec![19, 32776]).run().unwrap_err();
+        assert_eq!(
+            err.kind.to_string(),
+            "Value 32776 falls outside 15-bit range."
+        );
+    }
+
+    #[test]
+    fn assembler_kinds() {
+        let err = asm_to_u16(&PathBuf::from("examples/bad_parse.asm")).unwrap_err();
+        assert_eq!(
+            err.kind,
+            ErrorKind::Parse {
+                lexeme: "hello".to_string(),
+                expected: "a valid u16"
+            }
+        );
+
+        let err = asm_to_u16(&PathBuf::from("examples/invalid_number.asm")).unwrap_err();
+        assert_eq!(
+            err.kind,
+            ErrorKind::InvalidValue(32776, ValueSource::Source)
+        );
+        assert_eq!(err.kind.to_string(), "Value 32776 is invalid.");
+
+        let err = asm_to_u16(&PathBuf::from("examples/missing.asm")).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::Io(_)));
+        assert!(err.to_string().starts_with("IO error: "));
+
+        // only failed I/O is reported as such
+        let err = SynacorErr::new_plain(ErrorKind::Unformatted("x.asm".to_string()));
+        assert_eq!(
+            err.to_string(),
+            "x.asm is not formatted, run `synacor fmt` to fix it."
+        );
+    }
+
+    #[test]
+    fn distinct_exit_codes() {
+        let kinds = [
+            ErrorKind::InvalidOpcode(22),
+            ErrorKind::InvalidValue(32776, ValueSource::Operand),
+            ErrorKind::InvalidRegister(32776),
+            ErrorKind::StackUnderflow(OpName::Ret),
+            ErrorKind::MemoryOutOfRange(32768),
+            ErrorKind::InvalidAscii(256),
+            ErrorKind::DivisionByZero(1),
+            ErrorKind::Parse {
+                lexeme: String::new(),
+                expected: "",
+            },
+            ErrorKind::Patch(String::new()),
+            ErrorKind::Snapshot(String::new()),
+            ErrorKind::Unformatted(String::new()),
+            ErrorKind::LintDenied(1),
+            ErrorKind::Optimize(String::new()),
+            ErrorKind::Compile(String::new()),
+            ErrorKind::Replay(String::new()),
+            ErrorKind::EndOfInput,
+            ErrorKind::Solve(String::new()),
+            ErrorKind::Codes(String::new()),
+            ErrorKind::StepLimit(1),
+            ErrorKind::Io(String::new()),
+        ];
+
+        let codes: HashSet<u8> = kinds.iter().map(exit_code).collect();
+        assert_eq!(codes.len(), kinds.len());
+        assert!(!codes.contains(&0));
+    }
+
+    #[test]
+    fn runtime_context() -> Result<(), SynacorErr> {
+        let path = PathBuf::from("underflow.asm");
+        std::fs::write(&path, "push 0x0001\nset $1 0x0005\npop $0\npop $0\nhalt\n")?;
+        let (memory, map) = asm_to_u16_with_map(&path)?;
+        std::fs::remove_file(&path)?;
+
+        let err = VM::headless(memory).run().unwrap_err();
+        let context = err.context.as_ref().unwrap();
+        assert_eq!(context.instruction, "pop $0");
+        assert_eq!(context.registers[..2], [1, 5]);
+        assert_eq!(context.stack_len, 0);
+        assert_eq!(context.history, vec![0, 2, 5, 7]);
+
+        // with the source map the error points at the second `pop $0`
+        match err.with_source(&map).location {
+            Location::Code { start, end, .. } => assert_eq!((start, end), (33, 39)),
+            location => panic!("unexpected location {:?}", location),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn json_diagnostic() {
+        let err = asm_to_u16(&PathBuf::from("examples/bad_parse.asm")).unwrap_err();
+        let json = err.to_json();
+        assert_eq!(json["severity"], "error");
+        assert_eq!(json["message"], "\"hello\" does not parse as a valid u16.");
+        assert_eq!(json["file"], "examples/bad_parse.asm");
+        assert_eq!(json["span"]["start_line"], 1);
+        assert_eq!(json["span"]["start_column"], 11);
+        assert!(json["address"].is_null());
+
+        let err = VM::headless(vec![3, 32768]).run().unwrap_err();
+        let json = err.to_json();
+        assert_eq!(json["address"], 0);
+        assert!(json["span"].is_null());
+        assert_eq!(json["notes"][0], "instruction: 0x0000: pop $0");
+    }
+}
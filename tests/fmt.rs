@@ -0,0 +1,76 @@
+#[cfg(test)]
+mod test {
+    use std::path::{Path, PathBuf};
+    use synacor::convert::{bin_to_u16, u16_to_asm};
+    use synacor::error::{ErrorKind, SynacorErr};
+    use synacor::fmt::{changes, format};
+
+    #[test]
+    fn canonical_layout() -> Result<(), SynacorErr> {
+        let asm = "; header\n  0x0: set $1 4   ; c\nnoop noop\n\n0x5:add $0 $1 0x04\n";
+        let formatted = format(asm, Path::new("<asm>"))?;
+        assert_eq!(
+            formatted,
+            "; header\n\
+             0x0000: set  $1     0x0004        ; c\n        \
+             noop noop\n\
+             \n\
+             0x0005: add  $0     $1     0x0004\n"
+        );
+
+        // formatting is idempotent
+        assert_eq!(format(&formatted, Path::new("<asm>"))?, formatted);
+
+        let err = format("zz: noop", Path::new("<asm>")).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::Parse { .. }));
+
+        Ok(())
+    }
+
+    #[test]
+    fn disassembly_is_formatted() -> Result<(), SynacorErr> {
+        let converted_asm = PathBuf::from("fmt_challenge.asm");
+        u16_to_asm(
+            bin_to_u16(&PathBuf::from("examples/challenge.bin"))?,
+            &converted_asm,
+        )?;
+
+        let asm = std::fs::read_to_string(&converted_asm)?;
+        std::fs::remove_file(&converted_asm)?;
+        assert_eq!(format(&asm, &converted_asm)?, asm);
+
+        Ok(())
+    }
+
+    #[test]
+    fn line_changes() {
+        assert!(changes("a\nb\n", "a\nb\n").is_empty());
+        assert_eq!(changes("a\nb\n", "a\n"), vec![(2, vec!["b"], vec![])]);
+        assert_eq!(
+            changes("a\nb\n", "a\nb\n\nc\n"),
+            vec![(3, vec![], vec!["", "c"])]
+        );
+        assert_eq!(
+            changes("x\na\nb\ny\nc\n", "a\nB\nc\nz\n"),
+            vec![
+                (1, vec!["x"], vec![]),
+                (3, vec!["b", "y"], vec!["B"]),
+                (6, vec![], vec!["z"])
+            ]
+        );
+    }
+
+    #[test]
+    fn many_changes() {
+        // past the edit limit, everything between the common start and end is one change
+        let original: String = (0..8000).map(|i| format!("a{}\n", i)).collect();
+        let formatted: String = (0..8000).map(|i| format!("b{}\n", i)).collect();
+        let original = format!("start\n{}end\n", original);
+        let formatted = format!("start\n{}end\n", formatted);
+
+        let hunks = changes(&original, &formatted);
+        assert_eq!(hunks.len(), 1);
+        assert_eq!(hunks[0].0, 2);
+        assert_eq!((hunks[0].1.len(), hunks[0].2.len()), (8000, 8000));
+    }
+}
@@ -0,0 +1,57 @@
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+    use synacor::convert::assemble_lines;
+    use synacor::error::SynacorErr;
+    use synacor::lint::{lint, Level, Lint, LintConfig};
+
+    const ASM: &str = "pop $0
+out 0x00e9
+mod $1 $1 0x0000
+wmem 0x0003 0x0001
+jmp 0x000f
+noop
+jmp 0x0000
+halt
+";
+
+    #[test]
+    fn every_lint() -> Result<(), SynacorErr> {
+        let memory = assemble_lines(ASM, Path::new("<asm>"))?.concat();
+        let found: Vec<(Lint, usize)> = lint(&memory, &LintConfig::default())
+            .iter()
+            .map(|warning| (warning.lint, warning.addr))
+            .collect();
+
+        assert_eq!(
+            found,
+            vec![
+                (Lint::StackUnderflow, 0x00),
+                (Lint::NonAsciiOut, 0x02),
+                (Lint::ModByZero, 0x04),
+                (Lint::SelfModification, 0x08),
+                (Lint::MidInstructionJump, 0x0b),
+                // 0x000f is the zero operand of the second jmp, run as halt, so the rest is one run
+                (Lint::UnreachableCode, 0x0d),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn allowed_lints() -> Result<(), SynacorErr> {
+        let memory = assemble_lines(ASM, Path::new("<asm>"))?.concat();
+        let mut config = LintConfig::default();
+        config.set(Lint::UnreachableCode, Level::Allow);
+        config.set(Lint::ModByZero, Level::Deny);
+
+        let found = lint(&memory, &config);
+        assert_eq!(found.len(), 5);
+        assert!(found
+            .iter()
+            .all(|warning| warning.lint != Lint::UnreachableCode));
+
+        Ok(())
+    }
+}
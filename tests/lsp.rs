@@ -0,0 +1,59 @@
+#[cfg(test)]
+mod test {
+    use lsp_types::{HoverContents, Position, Range};
+    use std::path::Path;
+    use synacor::lsp::{completions, definition, diagnostics, hover, semantic_tokens};
+
+    const TEXT: &str = "0x0000: set $1 0x0004 ; start\n0x0003: jmp 0x0000\nadd $0 $1 zz\n";
+
+    #[test]
+    fn hover_and_definition() {
+        let hovered = hover(TEXT, Position::new(0, 9)).unwrap();
+        match hovered.contents {
+            HoverContents::Markup(markup) => {
+                assert!(markup
+                    .value
+                    .contains("set register <a> to the value of <b>"));
+                assert!(markup.value.contains("3 values wide"));
+            }
+            contents => panic!("unexpected hover {:?}", contents),
+        }
+
+        // the jump target is the label on the first line
+        assert_eq!(
+            definition(TEXT, Position::new(1, 14)),
+            Some(Range::new(Position::new(0, 0), Position::new(0, 6)))
+        );
+
+        // without labels the assembler decides where the target is
+        let unlabelled = "noop\nnoop\njmp 0x0001\n";
+        assert_eq!(
+            definition(unlabelled, Position::new(2, 6)),
+            Some(Range::new(Position::new(1, 0), Position::new(1, 4)))
+        );
+    }
+
+    #[test]
+    fn diagnostics_completion_and_tokens() {
+        let found = diagnostics(TEXT, Path::new("a.asm"));
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].message, "\"zz\" does not parse as a valid u16.");
+        assert_eq!(
+            found[0].range,
+            Range::new(Position::new(2, 10), Position::new(2, 12))
+        );
+
+        let mnemonics = completions("0x0000: ", Position::new(0, 8));
+        assert!(mnemonics.iter().any(|item| item.label == "wmem"));
+        let registers = completions("add ", Position::new(0, 4));
+        assert_eq!(registers.len(), 8);
+
+        // label, mnemonic, register, number, comment
+        let types: Vec<u32> = semantic_tokens(TEXT)
+            .iter()
+            .take(5)
+            .map(|token| token.token_type)
+            .collect();
+        assert_eq!(types, vec![0, 1, 2, 3, 4]);
+    }
+}
@@ -0,0 +1,80 @@
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+    use synacor::convert::assemble_lines;
+    use synacor::error::{ErrorKind, SynacorErr};
+    use synacor::optimize::optimize;
+    use synacor::vm::VM;
+
+    fn output(memory: Vec<u16>) -> Result<String, SynacorErr> {
+        let mut vm = VM::headless(memory);
+        vm.run()?;
+        Ok(vm.take_output())
+    }
+
+    #[test]
+    fn same_output_fewer_values() -> Result<(), SynacorErr> {
+        // a loop printing ABC, with jumps into removed code and through a jmp chain
+        let asm = "set $0 0x0041
+add $1 0x0001 0x0002
+noop
+set $2 $2
+out $0
+add $0 $0 0x0001
+add $1 $1 0x7fff
+jt $1 0x001a
+jmp 0x001c
+jmp 0x0008
+push $0
+pop $3
+out $3
+out 0x000a
+halt
+";
+        let memory = assemble_lines(asm, Path::new("<asm>"))?.concat();
+        let optimized = optimize(&memory)?;
+
+        assert_eq!(output(memory.clone())?, "ABCD\n");
+        assert_eq!(output(optimized.clone())?, "ABCD\n");
+        assert_eq!((memory.len(), optimized.len()), (37, 27));
+
+        Ok(())
+    }
+
+    #[test]
+    fn data_relocation() -> Result<(), SynacorErr> {
+        let asm = "noop\nrmem $0 0x0007\nout $0\nhalt\ndata 0x0058\n";
+        let memory = assemble_lines(asm, Path::new("<asm>"))?.concat();
+        let optimized = optimize(&memory)?;
+
+        assert_eq!(optimized, vec![15, 32768, 6, 19, 32768, 0, 0x58]);
+        assert_eq!(output(optimized)?, "X");
+
+        // the target of an indirect call is not known, so it cannot be moved
+        let memory = assemble_lines("set $0 0x0005\ncall $0\nret\n", Path::new("<asm>"))?.concat();
+        let err = optimize(&memory).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::Optimize(_)));
+
+        // code read as data changes as it is rewritten
+        let asm = "noop\nrmem $0 0x0000\nout $0\nhalt\n";
+        let memory = assemble_lines(asm, Path::new("<asm>"))?.concat();
+        let err = optimize(&memory).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::Optimize(_)));
+
+        // a return address pushed as a plain value is not relocated
+        let asm = "push 0x0004\nret\nhalt\nout 0x0041\nhalt\n";
+        let memory = assemble_lines(asm, Path::new("<asm>"))?.concat();
+        assert_eq!(output(memory.clone())?, "A");
+        let err = optimize(&memory).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::Optimize(_)));
+
+        // nor is the data read through a register
+        let asm = "noop\nset $1 0x000a\nrmem $0 $1\nout $0\nhalt\ndata 0x0058\n";
+        let memory = assemble_lines(asm, Path::new("<asm>"))?.concat();
+        assert_eq!(output(memory.clone())?, "X");
+        let err = optimize(&memory).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::Optimize(_)));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,73 @@
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+    use synacor::error::SynacorErr;
+    use synacor::patch::{apply, parse_patch_file, Hunk};
+
+    // 0x0000: out 0x0041
+    // 0x0002: add $0 $0 0x0001
+    // 0x0006: halt
+    fn program() -> Vec<u16> {
+        vec![19, 65, 9, 32768, 32768, 1, 0]
+    }
+
+    #[test]
+    fn instruction_boundaries() -> Result<(), SynacorErr> {
+        let mut memory = program();
+
+        // replacing `out` with two noops fits exactly
+        apply(&mut memory, &Hunk::from_asm(0, "noop noop")?, false)?;
+        assert_eq!(memory[..2], [21, 21]);
+
+        // a single noop would leave half of the `out`
+        let mut memory = program();
+        assert!(apply(&mut memory, &Hunk::from_asm(0, "noop")?, false).is_err());
+        assert_eq!(memory, program());
+
+        // and a replacement can't end mid-instruction either
+        let mut memory = program();
+        let truncated = Hunk {
+            addr: 0,
+            words: vec![9, 32768],
+        };
+        assert!(apply(&mut memory, &truncated, false).is_err());
+        apply(&mut memory, &truncated, true)?;
+        assert_eq!(memory[..2], [9, 32768]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn patch_file() -> Result<(), SynacorErr> {
+        let path = PathBuf::from("hunks.patch");
+        std::fs::write(
+            &path,
+            "; two hunks\n@0x0000\nout 0x0042\n@2 ; skip the add\nnoop noop\nnoop noop\n",
+        )?;
+
+        let hunks = parse_patch_file(&path)?;
+        std::fs::remove_file(&path)?;
+
+        assert_eq!(
+            hunks,
+            vec![
+                Hunk {
+                    addr: 0,
+                    words: vec![19, 66]
+                },
+                Hunk {
+                    addr: 2,
+                    words: vec![21, 21, 21, 21]
+                }
+            ]
+        );
+
+        let mut memory = program();
+        for hunk in hunks.iter() {
+            apply(&mut memory, hunk, false)?;
+        }
+        assert_eq!(memory, vec![19, 66, 21, 21, 21, 21, 0]);
+
+        Ok(())
+    }
+}
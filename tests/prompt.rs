@@ -0,0 +1,21 @@
+#[cfg(test)]
+mod test {
+    use synacor::prompt::{complete, words};
+
+    #[test]
+    fn completes_words_from_output() {
+        let output = "Things of interest here:\n- tablet\n- empty lantern\n\nThere are 2 exits:\n- doorway\n- south\n";
+        let words = words(output);
+
+        assert!(words.contains("lantern") && words.contains("things"));
+        assert!(!words.contains("of"));
+
+        assert_eq!(
+            complete("take ta", 7, &words),
+            (5, vec!["tablet".to_string()])
+        );
+        assert_eq!(complete("do", 2, &words), (0, vec!["doorway".to_string()]));
+        // a finished word has nothing left to complete
+        assert_eq!(complete("south", 5, &words), (0, vec![]));
+    }
+}
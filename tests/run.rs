@@ -0,0 +1,55 @@
+mod common;
+
+#[cfg(test)]
+mod test {
+    use crate::common::ECHO;
+    use std::io::Write;
+    use std::process::{Command, Output, Stdio};
+
+    fn run(path: &str, args: &[&str], stdin: &str) -> Output {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_synacor"))
+            .args(["--color", "never", "-f", "assembly", "-p", path, "run"])
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(stdin.as_bytes())
+            .unwrap();
+        child.wait_with_output().unwrap()
+    }
+
+    #[test]
+    fn end_of_piped_input() {
+        let path = std::env::temp_dir().join("run_end_of_piped_input.asm");
+        std::fs::write(&path, ECHO).unwrap();
+        let path = path.to_str().unwrap();
+
+        // no prompt is printed for a pipe, and running out of input halts cleanly
+        let out = run(path, &[], "ab\n");
+        assert!(out.status.success());
+        assert_eq!(String::from_utf8_lossy(&out.stdout), "ab\n");
+
+        let out = run(path, &["--on-eof", "error"], "ab\n");
+        assert_eq!(out.status.code(), Some(26));
+
+        let out = run(path, &["--on-eof", "feed", "--eof-line", "xq"], "");
+        assert!(out.status.success());
+        assert_eq!(String::from_utf8_lossy(&out.stdout), "x");
+
+        // a run halted by the end of input still records its halt
+        let log = std::env::temp_dir().join("run_end_of_piped_input.log");
+        let out = run(path, &["--record", log.to_str().unwrap()], "ab\n");
+        assert!(out.status.success());
+        let recorded = std::fs::read_to_string(&log).unwrap();
+        assert!(recorded.lines().last().unwrap().starts_with("halt "));
+
+        std::fs::remove_file(log).unwrap();
+        std::fs::remove_file(path).unwrap();
+    }
+}
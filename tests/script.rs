@@ -0,0 +1,60 @@
+#[cfg(test)]
+mod test {
+    use std::path::{Path, PathBuf};
+    use std::time::Duration;
+    use synacor::convert::assemble_lines;
+    use synacor::error::{ErrorKind, SynacorErr};
+    use synacor::script::{Script, Step};
+    use synacor::snapshot::Snapshot;
+    use synacor::vm::{State, VM};
+
+    #[test]
+    fn parse() -> Result<(), SynacorErr> {
+        let text = "# walk to the ladder\n!delay 0\ntake tablet  # pick it up\n\n!set $7 0x10\n!pause\n!save at_ladder.snap\n";
+        let script = Script::parse(text, Path::new("moves.txt"))?;
+
+        assert_eq!(
+            Vec::from(script.steps),
+            vec![
+                Step::Delay(Duration::ZERO),
+                Step::Line("take tablet".to_string()),
+                Step::SetRegister(7, 0x10),
+                Step::Pause,
+                Step::Save(PathBuf::from("at_ladder.snap")),
+            ]
+        );
+
+        let err = Script::parse("north\n!set $8 1\n", Path::new("moves.txt")).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::Parse { .. }));
+        assert_eq!(err.to_json()["span"]["start_line"], 2);
+        Ok(())
+    }
+
+    #[test]
+    fn headless_run() -> Result<(), SynacorErr> {
+        // echo input back forever
+        let memory = assemble_lines("in $0\nout $0\njmp 0x0000\n", Path::new("<asm>"))?.concat();
+        let snap = std::env::temp_dir().join("script_headless_run.snap");
+        let text = format!(
+            "one\n!set $7 0x0001\n!pause\ntwo\n!save {}\n",
+            snap.display()
+        );
+
+        let mut vm = VM::headless(memory);
+        vm.set_script(Script::parse(&text, Path::new("moves.txt"))?);
+
+        // a pause stops a headless VM until it is fed
+        assert_eq!(vm.run()?, State::AwaitingInput);
+        assert_eq!(vm.take_output(), "one\n");
+        assert_eq!(vm.registers()[7], 1);
+
+        vm.feed("fed\n");
+        assert_eq!(vm.run()?, State::AwaitingInput);
+        assert_eq!(vm.take_output(), "fed\ntwo\n");
+
+        let saved = Snapshot::load(&snap)?;
+        std::fs::remove_file(&snap)?;
+        assert_eq!((saved.addr, saved.registers[7]), (0, 1));
+        Ok(())
+    }
+}
@@ -0,0 +1,51 @@
+mod common;
+
+#[cfg(test)]
+mod test {
+    use crate::common::ECHO;
+    use std::path::Path;
+    use synacor::convert::assemble_lines;
+    use synacor::error::{ErrorKind, SynacorErr};
+    use synacor::script::Script;
+    use synacor::session::{Event, Recorder, Replay};
+    use synacor::vm::{State, VM};
+
+    #[test]
+    fn record_and_replay() -> Result<(), SynacorErr> {
+        let memory = assemble_lines(ECHO, Path::new("<asm>"))?.concat();
+        let log = std::env::temp_dir().join("session_record_and_replay.log");
+
+        let mut vm = VM::headless(memory.clone());
+        vm.set_script(Script::parse("ab\nq\n", Path::new("moves.txt"))?);
+        vm.record(Recorder::create(&log)?);
+        assert_eq!(vm.run()?, State::Halted);
+        assert_eq!(vm.take_output(), "ab\n");
+
+        let replay = Replay::load(&log)?;
+        std::fs::remove_file(&log)?;
+        let counts: Vec<u64> = replay
+            .events
+            .iter()
+            .map(|event| match event {
+                Event::Input { count, .. } | Event::Halt { count, .. } => *count,
+            })
+            .collect();
+        assert_eq!(counts, vec![0, 15, 18]);
+
+        let mut vm = VM::headless(memory);
+        vm.replay(replay.clone());
+        assert_eq!(vm.run()?, State::Halted);
+        assert_eq!(vm.take_output(), "ab\n");
+        assert_eq!(vm.instruction_count(), 19);
+
+        // a patched binary that prints differently no longer matches
+        let patched = ECHO.replace("out $0", "out $1");
+        let mut vm = VM::headless(assemble_lines(&patched, Path::new("<asm>"))?.concat());
+        vm.replay(replay);
+        let err = vm.run().unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::Replay(_)));
+        assert_eq!(err.addr(), Some(0));
+
+        Ok(())
+    }
+}
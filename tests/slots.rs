@@ -0,0 +1,51 @@
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+    use synacor::convert::bin_to_u16;
+    use synacor::error::SynacorErr;
+    use synacor::slots::{scrape, value_of, Equation};
+
+    #[test]
+    fn parse() -> Result<(), SynacorErr> {
+        let path = Path::new("<equation>");
+        let equation = Equation::parse("(_ - 1) * _^2 = 18", path)?;
+
+        assert_eq!((equation.slots, equation.target), (2, 18));
+        assert!(equation.holds(&[3, 3]) && !equation.holds(&[3, 2]));
+        assert_eq!(equation.fill(&[3, 3]), "(3 - 1) * 3^2 = 18");
+
+        assert!(Equation::parse("_ + = 1", path).is_err());
+        assert!(Equation::parse("_ + _", path).is_err());
+        assert_eq!(value_of("It has seven dots on one side."), Some(7));
+        assert_eq!(value_of("It has a pentagon on one side."), Some(5));
+        Ok(())
+    }
+
+    #[test]
+    fn solves_monument() -> Result<(), SynacorErr> {
+        let memory = bin_to_u16(&"examples/challenge.bin".into())?;
+        let (equation, coins) = scrape(memory, "coin")?;
+        let equation = equation.expect("the monument's equation is among the strings");
+
+        assert_eq!(equation.fill(&[]), "_ + _ * _^2 + _^3 - _ = 399");
+        assert_eq!(coins.len(), 5);
+
+        let order: Vec<&str> = equation
+            .solve(&coins)
+            .expect("the coins have a solution")
+            .iter()
+            .map(|coin| coin.name.as_str())
+            .collect();
+        assert_eq!(
+            order,
+            vec![
+                "blue coin",
+                "red coin",
+                "shiny coin",
+                "concave coin",
+                "corroded coin"
+            ]
+        );
+        Ok(())
+    }
+}
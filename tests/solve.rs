@@ -0,0 +1,110 @@
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+    use synacor::compile::compile;
+    use synacor::convert::{assemble_lines, bin_to_u16};
+    use synacor::error::{ErrorKind, SynacorErr};
+    use synacor::solve::{parse_inventory, solve};
+
+    // a key in the hall opens the vault door in the yard, going by the first letter of each
+    // command
+    const VAULT: &str = r#"
+fn main() {
+    let room = 0;
+    let key = 0;
+    let show = 1;
+    while (1) {
+        if (show && room == 0) {
+            print("\n== Hall ==\nA bare hall.\n\n");
+            if (!key) { print("Things of interest here:\n- key\n\n"); }
+            print("There is 1 exit:\n- north\n");
+        }
+        if (show && room == 1) {
+            print("\n== Yard ==\nA locked door.\n\nThere is 1 exit:\n- south\n");
+        }
+        show = 0;
+        print("\nWhat do you do?\n");
+
+        let c = getc();
+        let d = c;
+        while (d != '\n') { d = getc(); }
+        if (c == 'l') { show = 1; }
+        if (c == 'i') {
+            print("\nYour inventory:\n");
+            if (key) { print("- key\n"); }
+        }
+        if (c == 'n' && room == 0) { room = 1; show = 1; }
+        if (c == 's' && room == 1) { room = 0; show = 1; }
+        if (c == 't' && room == 0 && !key) { key = 1; print("Taken.\n"); }
+        if (c == 'u' && key && room == 1) { print("The door opens onto the vault.\n"); }
+    }
+}
+"#;
+
+    // the treasure is only seen from the fall that ends the game
+    const LEDGE: &str = r#"
+fn main() {
+    while (1) {
+        print("\n== Ledge ==\nA narrow ledge.\n\nThere are 2 exits:\n- east\n- north\n");
+        print("\nWhat do you do?\n");
+
+        let c = getc();
+        let d = c;
+        while (d != '\n') { d = getc(); }
+        if (c == 'i') { print("\nYour inventory:\n"); }
+        if (c == 'e') { print("You glimpse the treasure as you fall.\n"); halt(); }
+    }
+}
+"#;
+
+    #[test]
+    fn inventory() {
+        let output = "Your inventory:\n- tablet\n\nWhat do you do?\n\nYour inventory:\n- tablet\n- empty lantern\n\nWhat do you do?\n";
+        assert_eq!(
+            parse_inventory(output),
+            Some(vec!["tablet".to_string(), "empty lantern".to_string()])
+        );
+        assert_eq!(parse_inventory("Taken.\n"), None);
+    }
+
+    #[test]
+    fn solves_vault() -> Result<(), SynacorErr> {
+        let asm = compile(VAULT, Path::new("vault.syn"))?;
+        let memory = assemble_lines(&asm, Path::new("vault.asm"))?.concat();
+
+        let solution = solve(memory.clone(), "vault.", None, 100)?;
+        assert_eq!(solution.commands, vec!["take key", "north", "use key"]);
+        assert_eq!(
+            solution.to_script("vault."),
+            "# 3 commands until the game prints \"vault.\"\ntake key\nnorth\nuse key\n"
+        );
+
+        // only shown by the inventory typed after taking it
+        let solution = solve(memory.clone(), "inventory:\n- key", None, 100)?;
+        assert_eq!(solution.commands, vec!["take key", "inv"]);
+
+        let err = solve(memory, "treasure", None, 100).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::Solve(_)));
+        Ok(())
+    }
+
+    #[test]
+    fn maze_rooms_told_apart() -> Result<(), SynacorErr> {
+        // the ruins lie past a maze of rooms that read the same
+        let memory = bin_to_u16(&"examples/challenge.bin".into())?;
+        let solution = solve(memory, "== Ruins ==", None, 1000)?;
+        assert_eq!(solution.commands.len(), 26);
+        assert_eq!(solution.commands.last().unwrap(), "west");
+        Ok(())
+    }
+
+    #[test]
+    fn halted_output_is_dropped() -> Result<(), SynacorErr> {
+        let asm = compile(LEDGE, Path::new("ledge.syn"))?;
+        let memory = assemble_lines(&asm, Path::new("ledge.asm"))?.concat();
+
+        let err = solve(memory, "treasure", None, 100).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::Solve(_)));
+        Ok(())
+    }
+}
@@ -0,0 +1,41 @@
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+    use synacor::convert::bin_to_u16;
+    use synacor::error::SynacorErr;
+    use synacor::strings::{changed_ranges, decrypted_image, find_strings, DecodedString};
+
+    #[test]
+    fn ranges_and_strings() {
+        let original = vec![0, 0, 0, 0, 0, 0, 9];
+        let current = vec![0, 3, 104, 105, 33, 0, 9, 1];
+
+        assert_eq!(changed_ranges(&original, &current), vec![1..5, 7..8]);
+        assert_eq!(
+            find_strings(&current, 1..5, 2),
+            vec![DecodedString {
+                addr: 1,
+                text: "hi!".to_string()
+            }]
+        );
+        assert!(find_strings(&current, 1..5, 4).is_empty());
+    }
+
+    #[test]
+    fn challenge_binary() -> Result<(), SynacorErr> {
+        let memory = bin_to_u16(&PathBuf::from("examples/challenge.bin"))?;
+        let decrypted = decrypted_image(memory.clone())?;
+
+        let strings: Vec<DecodedString> = changed_ranges(&memory, &decrypted)
+            .into_iter()
+            .flat_map(|range| find_strings(&decrypted, range, 4))
+            .collect();
+
+        assert!(strings.contains(&DecodedString {
+            addr: 0x1814,
+            text: "Foothills".to_string()
+        }));
+
+        Ok(())
+    }
+}
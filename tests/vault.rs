@@ -0,0 +1,53 @@
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+    use synacor::convert::bin_to_u16;
+    use synacor::error::SynacorErr;
+    use synacor::script::Script;
+    use synacor::vault::{scrape, Cell, Vault};
+    use synacor::vm::{State, VM};
+
+    const GRID: &str = "# the vault lock in challenge.bin
+target 30
+*  8  -  1
+4  *  11 *
++  4  -  18
+22 -  9  *
+";
+
+    #[test]
+    fn solves_grid() -> Result<(), SynacorErr> {
+        let vault = Vault::parse(GRID, Path::new("vault.txt"))?;
+        assert_eq!((vault.start, vault.door), ((3, 0), (0, 3)));
+
+        let moves = vault.solve().expect("the door can be reached");
+        assert_eq!(
+            moves,
+            vec![
+                "north", "east", "east", "north", "west", "south", "east", "east", "west", "north",
+                "north", "east"
+            ]
+        );
+        assert_eq!(vault.equation(&moves), "22 + 4 - 11 * 4 - 18 - 11 - 1 = 30");
+
+        assert!(Vault::parse("target 30\n22 / 1\n", Path::new("vault.txt")).is_err());
+        assert!(Vault::parse("* 1\n22 +\n", Path::new("vault.txt")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn scrapes_rooms() -> Result<(), SynacorErr> {
+        let memory = bin_to_u16(&"examples/challenge.bin".into())?;
+        let mut vm = VM::headless(memory);
+
+        // move straight to the antechamber's room record
+        let script = "admin poke 0x0ac2 0x0a55\nadmin poke 0x0ac3 0x0a55\n";
+        vm.set_script(Script::parse(script, Path::new("antechamber.txt"))?);
+        assert_eq!(vm.run()?, State::AwaitingInput);
+
+        let vault = scrape(&vm.snapshot())?;
+        assert_eq!(vault, Vault::parse(GRID, Path::new("vault.txt"))?);
+        assert_eq!(vault.rows[1][2], Cell::Num(11));
+        Ok(())
+    }
+}
@@ -0,0 +1,29 @@
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+    use synacor::convert::assemble_lines;
+    use synacor::error::{ErrorKind, SynacorErr};
+    use synacor::vm::{State, VM};
+
+    #[test]
+    fn writes_last_register() -> Result<(), SynacorErr> {
+        let asm = "set $7 0x0041\nadd $7 $7 0x0001\nout $7\nhalt\n";
+        let memory = assemble_lines(asm, Path::new("<asm>"))?.concat();
+
+        let mut vm = VM::headless(memory);
+        assert_eq!(vm.run()?, State::Halted);
+        assert_eq!(vm.take_output(), "B");
+        assert_eq!(vm.registers()[7], 0x42);
+        Ok(())
+    }
+
+    #[test]
+    fn step_limit() {
+        // jmp 0x0000
+        let err = VM::headless(vec![6, 0]).run_for(1000).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::StepLimit(1000));
+
+        let mut vm = VM::headless(vec![19, 65, 0]);
+        assert_eq!(vm.run_for(1000).unwrap(), State::Halted);
+    }
+}
@@ -0,0 +1,90 @@
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+    use synacor::compile::compile;
+    use synacor::convert::assemble_lines;
+    use synacor::error::SynacorErr;
+    use synacor::world::{explore, parse_room, Dest, Exit, Room};
+
+    // two rooms and a pit, going by the first letter of each command
+    const ADVENTURE: &str = r#"
+fn main() {
+    let room = 0;
+    while (1) {
+        if (room == 0) {
+            print("== Hall ==\nA bare hall.\n\nThings of interest here:\n- lamp\n\n");
+            print("There are 2 exits:\n- north\n- east\n\nWhat do you do?\n");
+        } else {
+            print("== Yard ==\nLong grass.\n\nThere is 1 exit:\n- south\n\nWhat do you do?\n");
+        }
+        let c = getc();
+        let d = c;
+        while (d != '\n') { d = getc(); }
+        if (c == 'n') { room = 1; }
+        if (c == 's') { room = 0; }
+        if (c == 'e') { print("You fall into a pit.\n"); halt(); }
+    }
+}
+"#;
+
+    #[test]
+    fn parse() {
+        let output = "Taken.\n\n== Moss cavern ==\nYou are standing in a large cavern.\nIt is bright.\n\nThings of interest here:\n- empty lantern\n\nThere is 1 exit:\n- west\n\nWhat do you do?\n";
+
+        assert_eq!(
+            parse_room(output),
+            Some(Room {
+                title: "Moss cavern".to_string(),
+                description: "You are standing in a large cavern. It is bright.".to_string(),
+                items: vec!["empty lantern".to_string()],
+                exits: vec!["west".to_string()],
+            })
+        );
+        assert_eq!(parse_room("Taken.\n\nWhat do you do?\n"), None);
+    }
+
+    #[test]
+    fn explore_adventure() -> Result<(), SynacorErr> {
+        let asm = compile(ADVENTURE, Path::new("adventure.syn"))?;
+        let memory = assemble_lines(&asm, Path::new("adventure.asm"))?.concat();
+        let world = explore(memory.clone(), None, 10)?;
+
+        let titles: Vec<&str> = world.rooms.iter().map(|room| room.title.as_str()).collect();
+        assert_eq!(titles, vec!["Hall", "Yard"]);
+        assert_eq!(world.rooms[0].items, vec!["lamp".to_string()]);
+
+        let exit = |from, name: &str, to| Exit {
+            from,
+            name: name.to_string(),
+            to,
+        };
+        assert_eq!(
+            world.exits,
+            vec![
+                exit(0, "north", Dest::Room(1)),
+                exit(0, "east", Dest::End),
+                exit(1, "south", Dest::Room(0))
+            ]
+        );
+
+        let dot = world.to_dot();
+        assert!(dot.contains("room_0 -> end [label=\"east\"];"));
+        assert_eq!(world.to_json()["rooms"][1]["exits"]["south"], 0);
+        assert!(world.to_json()["rooms"][0]["exits"]["east"].is_null());
+
+        // past the room limit, exits are left unexplored rather than ending the game
+        let world = explore(memory, None, 1)?;
+        assert_eq!(
+            world.exits,
+            vec![
+                exit(0, "north", Dest::Unexplored),
+                exit(0, "east", Dest::End)
+            ]
+        );
+        assert!(world
+            .to_dot()
+            .contains("room_0 -> unexplored [label=\"north\", style=dashed];"));
+        assert_eq!(world.to_json()["rooms"][0]["exits"]["north"], "unexplored");
+        Ok(())
+    }
+}
@@ -0,0 +1,34 @@
+#[cfg(test)]
+mod test {
+    use synacor::xref::{RefKind, Reference, Xrefs};
+
+    #[test]
+    fn small_program() {
+        // 0x0000: call 0x0007
+        // 0x0002: rmem $0 0x0009
+        // 0x0005: jmp 0x0007
+        // 0x0007: wmem 0x0009 $0
+        // 0x000a: ret
+        let memory = vec![17, 7, 15, 32768, 9, 6, 7, 16, 9, 32768, 18];
+        let xrefs = Xrefs::new(&memory);
+
+        assert_eq!(
+            xrefs.to(7),
+            &[
+                Reference {
+                    from: 0,
+                    kind: RefKind::Call
+                },
+                Reference {
+                    from: 5,
+                    kind: RefKind::Jump
+                }
+            ]
+        );
+        assert_eq!(
+            xrefs.comment(9).as_deref(),
+            Some("; xref read 0x0002, write 0x0007")
+        );
+        assert!(xrefs.to(2).is_empty());
+    }
+}